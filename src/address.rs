@@ -0,0 +1,46 @@
+// адрес = префикс сети + Base58Check(версия || RIPEMD160(SHA256(публичный ключ)))
+// контрольная сумма не дает отправить монеты на адрес с опечаткой.
+// у мультиподписного адреса своя версия, а хешируются порог и все открытые ключи
+
+use ripemd::Ripemd160;
+use sha2::{Digest, Sha256};
+
+pub const PUBLIC_KEY_HASH_VERSION: u8 = 0x00;
+pub const MULTISIG_VERSION: u8 = 0x05;
+
+pub fn from_public_key(public_key: &[u8], prefix: &str) -> String {
+    encode(PUBLIC_KEY_HASH_VERSION, &hash160(public_key), prefix)
+}
+
+// ключи хешируются в переданном порядке, поэтому кошелек сортирует их при создании адреса
+pub fn from_multisig(threshold: u8, public_keys: &[Vec<u8>], prefix: &str) -> String {
+    let mut data = vec![threshold, public_keys.len() as u8];
+    for public_key in public_keys {
+        data.extend_from_slice(public_key);
+    }
+    encode(MULTISIG_VERSION, &hash160(&data), prefix)
+}
+
+pub fn encode(version: u8, hash: &[u8; 20], prefix: &str) -> String {
+    let encoded = bs58::encode(hash).with_check_version(version).into_string();
+    format!("{}{}", prefix, encoded)
+}
+
+// возвращает версию и хеш, если адрес принадлежит сети и контрольная сумма верна
+pub fn decode(address: &str, prefix: &str) -> Option<(u8, [u8; 20])> {
+    let encoded = address.strip_prefix(prefix)?;
+    let data = bs58::decode(encoded).with_check(None).into_vec().ok()?;
+    let (version, hash) = data.split_first()?;
+    if *version != PUBLIC_KEY_HASH_VERSION && *version != MULTISIG_VERSION {
+        return None;
+    }
+    Some((*version, hash.try_into().ok()?))
+}
+
+pub fn is_valid(address: &str, prefix: &str) -> bool {
+    decode(address, prefix).is_some()
+}
+
+pub fn hash160(data: &[u8]) -> [u8; 20] {
+    Ripemd160::digest(Sha256::digest(data)).into()
+}
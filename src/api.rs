@@ -1,59 +1,592 @@
-use crate::blockchain::Blockchain;
-use crate::p2p::{Message, P2P};
+use crate::address;
+use crate::auth::{self, ApiAuth, Role};
+use crate::limits::{self, Limits, MAX_BODY_SIZE};
+use crate::metrics::{self, Metrics};
+use crate::block::Block;
+use crate::blockchain::{Balance, Blockchain, Ledger, TxLocation};
+use crate::events::Event;
+use crate::i18n::{self, fail, Localize, Message};
+use crate::keystore::{Kdf, KdfKind};
+use crate::p2p::P2P;
+use crate::rpc;
+use crate::partial_tx::{PartialTransaction, PartialTxError};
 use crate::transaction::Transaction;
-use crate::wallet::Wallet;
-use axum::extract::{Path, State};
-use axum::response::IntoResponse;
+use crate::wallet::{KeyFormat, SignatureScheme, SignedMessage};
+use crate::wallet_manager::{WalletError, WalletManager, DEFAULT_UNLOCK_TIMEOUT};
+use axum::extract::ws::{self, WebSocket, WebSocketUpgrade};
+use axum::body::Body;
+use axum::async_trait;
+use axum::extract::rejection::{JsonRejection, PathRejection, QueryRejection};
+use axum::extract::{ConnectInfo, DefaultBodyLimit, FromRef, FromRequest, FromRequestParts, Request, State};
+use axum::http::header::{AUTHORIZATION, CONTENT_TYPE};
+use axum::http::request::Parts;
+use axum::http::{HeaderName, Method, StatusCode};
+use axum::middleware;
+use axum::response::sse::{self, KeepAlive, Sse};
+use axum::response::{IntoResponse, Response};
 use axum::routing::{get, post, put};
-use axum::{Json, Router};
-use serde::Deserialize;
+use axum::Router;
+use axum_server::tls_rustls::RustlsConfig;
+use axum_server::Handle;
+use serde::de::DeserializeOwned;
+use serde::{Deserialize, Serialize};
+use std::collections::HashSet;
+use std::convert::Infallible;
+use std::error::Error;
+use std::net::SocketAddr;
+use std::sync::atomic::Ordering;
 use std::sync::{Arc, Mutex};
+use std::time::Duration;
 use tokio::signal;
+use tokio::sync::broadcast::error::RecvError;
+use tokio::sync::broadcast::Receiver;
+use tokio_stream::wrappers::BroadcastStream;
+use tokio_stream::StreamExt;
+use tower_http::classify::{ServerErrorsAsFailures, SharedClassifier};
+use tower_http::cors::{AllowOrigin, CorsLayer};
+use tower_http::trace::{DefaultOnResponse, MakeSpan, TraceLayer};
+use tracing::{error, info, info_span, Level};
+
+// размер страницы в списках
+const DEFAULT_PAGE_LIMIT: usize = 20;
+const MAX_PAGE_LIMIT: usize = 100;
 
 #[derive(Deserialize)]
 pub struct NewTransactionData {
-    pub from_file: String,
+    // имя разблокированного кошелька в каталоге узла
+    pub wallet: String,
+    // номер адреса в кошельке
+    #[serde(default)]
+    pub account: u32,
     pub to: String,
     pub amount: f64,
+    #[serde(default)]
+    pub signature_type: SignatureScheme,
+}
+
+#[derive(Deserialize)]
+pub struct CreateWalletData {
     pub password: String,
+    // фраза для восстановления существующего кошелька
+    pub mnemonic: Option<String>,
+    // функция получения ключа из пароля для нового файла кошелька
+    #[serde(default)]
+    pub kdf: KdfKind,
 }
 
 #[derive(Deserialize)]
-pub struct WalletAccessData {
-    pub file_name: String,
+pub struct UnlockWalletData {
     pub password: String,
+    // время разблокировки в секундах
+    pub timeout: Option<u64>,
+}
+
+#[derive(Deserialize)]
+pub struct AccountLabelData {
+    pub label: Option<String>,
+}
+
+#[derive(Deserialize)]
+pub struct WatchAddressData {
+    // адрес или открытый ключ в hex
+    pub address: String,
+    pub label: Option<String>,
+}
+
+#[derive(Deserialize)]
+pub struct MultisigWalletData {
+    pub threshold: u8,
+    // сжатые открытые ключи участников в hex
+    pub public_keys: Vec<String>,
+}
+
+#[derive(Deserialize)]
+pub struct MultisigTxData {
+    pub to: String,
+    pub amount: f64,
+    #[serde(default)]
+    pub signature_type: SignatureScheme,
+}
+
+#[derive(Deserialize)]
+pub struct SignTransactionData {
+    #[serde(default)]
+    pub account: u32,
+    pub transaction: Transaction,
+}
+
+#[derive(Deserialize)]
+pub struct CombineTransactionsData {
+    pub transactions: Vec<Transaction>,
+}
+
+#[derive(Deserialize)]
+pub struct SendTransactionData {
+    pub transaction: Transaction,
+}
+
+#[derive(Deserialize)]
+pub struct PartialTxData {
+    pub from: String,
+    pub to: String,
+    pub amount: f64,
+    #[serde(default)]
+    pub signature_type: SignatureScheme,
+}
+
+#[derive(Deserialize)]
+pub struct SignMessageData {
+    pub wallet: String,
+    #[serde(default)]
+    pub account: u32,
+    pub message: String,
+    #[serde(default)]
+    pub signature_type: SignatureScheme,
+}
+
+#[derive(Deserialize)]
+pub struct EventFilter {
+    // адреса через запятую; без фильтра приходят события по всем адресам
+    pub addresses: Option<String>,
+}
+
+#[derive(Deserialize)]
+pub struct PageParams {
+    #[serde(default)]
+    pub offset: usize,
+    pub limit: Option<usize>,
+}
+
+#[derive(Deserialize)]
+pub struct ChangePasswordData {
+    pub password: String,
+    pub new_password: String,
+    #[serde(default)]
+    pub kdf: KdfKind,
+}
+
+#[derive(Deserialize)]
+pub struct ExportKeyData {
+    pub password: String,
+    #[serde(default)]
+    pub account: u32,
+    #[serde(default)]
+    pub format: KeyFormat,
+}
+
+#[derive(Deserialize)]
+pub struct ImportKeyData {
+    pub password: String,
+    pub private_key: String,
+    #[serde(default)]
+    pub kdf: KdfKind,
+}
+
+#[derive(Serialize)]
+pub struct WalletCreated {
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub mnemonic: Option<String>,
+    pub address: String,
+}
+
+#[derive(Serialize)]
+pub struct TxInfo {
+    #[serde(flatten)]
+    pub location: TxLocation,
+    pub confirmations: u64,
+    pub transaction: Transaction,
+}
+
+#[derive(Serialize)]
+pub struct BlockInfo {
+    pub height: u64,
+    pub hash: String,
+    pub prev_hash: String,
+    pub timestamp: u128,
+    pub nonce: u64,
+    pub confirmations: u64,
+    pub txids: Vec<String>,
+    // в списках блоков транзакции не передаются
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub transactions: Option<Vec<Transaction>>,
+}
+
+#[derive(Serialize)]
+pub struct BlockPage {
+    pub total: u64,
+    pub offset: usize,
+    pub limit: usize,
+    pub blocks: Vec<BlockInfo>,
+}
+
+#[derive(Serialize)]
+pub struct AddressHistory {
+    pub address: String,
+    pub total: usize,
+    pub offset: usize,
+    pub limit: usize,
+    pub txs: Vec<TxInfo>,
+}
+
+#[derive(Serialize)]
+pub struct ChainInfo {
+    pub network_id: String,
+    pub genesis_hash: String,
+    pub ledger: Ledger,
+    pub difficulty: usize,
+    pub height: u64,
+    pub tip_hash: String,
+}
+
+// блок задается высотой или хешем
+#[derive(Deserialize)]
+#[serde(untagged)]
+pub enum BlockId {
+    Height(u64),
+    Hash(String),
+}
+
+// текстовый ответ API: код, не зависящий от языка, и сообщение на языке запроса
+#[derive(Serialize, Deserialize)]
+pub struct ApiMessage {
+    pub code: String,
+    pub message: String,
+}
+
+impl ApiMessage {
+    pub fn new(message: &impl Localize) -> Self {
+        ApiMessage { code: message.code().to_string(), message: message.text(i18n::lang()) }
+    }
+}
+
+pub fn reply(status: StatusCode, message: impl Localize) -> Response {
+    (status, Json(ApiMessage::new(&message))).into_response()
+}
+
+// обертки над извлекателями axum: ошибки разбора запроса отдаются как ApiMessage
+// с кодом из каталога и на языке запроса, а не текстом axum
+pub struct Json<T>(pub T);
+
+#[async_trait]
+impl<T: DeserializeOwned, S: Send + Sync> FromRequest<S> for Json<T>
+where
+    AppState: FromRef<S>,
+{
+    type Rejection = Response;
+
+    async fn from_request(req: Request, state: &S) -> Result<Self, Self::Rejection> {
+        match axum::Json::from_request(req, state).await {
+            Ok(axum::Json(value)) => Ok(Json(value)),
+            Err(rejection) => {
+                let status = rejection.status();
+                let message = match &rejection {
+                    JsonRejection::JsonSyntaxError(e) => Message::InvalidJson(cause(e)),
+                    JsonRejection::JsonDataError(e) => Message::InvalidBody(cause(e)),
+                    JsonRejection::MissingJsonContentType(_) => Message::UnsupportedContentType,
+                    // тело без Content-Length не проверить заранее в rate_limit, его отказ считается здесь
+                    _ if status == StatusCode::PAYLOAD_TOO_LARGE => {
+                        AppState::from_ref(state).limits.rejected_body_size.fetch_add(1, Ordering::Relaxed);
+                        Message::PayloadTooLarge
+                    }
+                    _ => Message::InvalidBody(rejection.body_text()),
+                };
+                Err(reply(status, message))
+            }
+        }
+    }
+}
+
+impl<T: Serialize> IntoResponse for Json<T> {
+    fn into_response(self) -> Response {
+        axum::Json(self.0).into_response()
+    }
+}
+
+pub struct Path<T>(pub T);
+
+#[async_trait]
+impl<T: DeserializeOwned + Send, S: Send + Sync> FromRequestParts<S> for Path<T> {
+    type Rejection = Response;
+
+    async fn from_request_parts(parts: &mut Parts, state: &S) -> Result<Self, Self::Rejection> {
+        match axum::extract::Path::from_request_parts(parts, state).await {
+            Ok(axum::extract::Path(value)) => Ok(Path(value)),
+            Err(rejection) => {
+                let detail = match &rejection {
+                    PathRejection::FailedToDeserializePathParams(e) => e.kind().to_string(),
+                    _ => rejection.body_text(),
+                };
+                Err(reply(rejection.status(), Message::InvalidPath(detail)))
+            }
+        }
+    }
+}
+
+pub struct Query<T>(pub T);
+
+#[async_trait]
+impl<T: DeserializeOwned, S: Send + Sync> FromRequestParts<S> for Query<T> {
+    type Rejection = Response;
+
+    async fn from_request_parts(parts: &mut Parts, state: &S) -> Result<Self, Self::Rejection> {
+        match axum::extract::Query::from_request_parts(parts, state).await {
+            Ok(axum::extract::Query(value)) => Ok(Query(value)),
+            Err(rejection) => {
+                let detail = match &rejection {
+                    QueryRejection::FailedToDeserializeQueryString(e) => cause(e),
+                    _ => rejection.body_text(),
+                };
+                Err(reply(rejection.status(), Message::InvalidQuery(detail)))
+            }
+        }
+    }
+}
+
+// текст ошибки serde без английского вступления axum
+fn cause(rejection: &dyn Error) -> String {
+    rejection.source().map(ToString::to_string).unwrap_or_else(|| rejection.to_string())
+}
+
+// ошибка операции, общей для REST и JSON-RPC
+pub enum ApiError {
+    InvalidParams(Message),
+    NotFound(Message),
+    Rejected(Message),
+}
+
+impl IntoResponse for ApiError {
+    fn into_response(self) -> Response {
+        match self {
+            ApiError::InvalidParams(message) | ApiError::Rejected(message) => reply(StatusCode::BAD_REQUEST, message),
+            ApiError::NotFound(message) => reply(StatusCode::NOT_FOUND, message),
+        }
+    }
+}
+
+#[derive(Serialize)]
+pub struct SupplyInfo {
+    pub height: u64,
+    pub circulating_supply: f64,
+    pub max_supply: f64,
+    pub next_block_subsidy: f64,
+}
+
+pub struct ApiConfig {
+    pub addr: SocketAddr,
+    // пути к сертификату и ключу в PEM; без них API работает по HTTP
+    pub tls: Option<(String, String)>,
+    // источники, из которых браузер может вызывать API; * разрешает любой
+    pub cors_origins: Vec<String>,
+    pub auth: Arc<ApiAuth>,
+    pub limits: Arc<Limits>,
 }
 
 #[derive(Clone)]
 pub struct AppState {
     pub blockchain: Arc<Mutex<Blockchain>>,
-    pub p2p: Arc<P2P>
+    pub p2p: Arc<P2P>,
+    pub wallets: Arc<WalletManager>,
+    pub auth: Arc<ApiAuth>,
+    pub limits: Arc<Limits>,
+    pub metrics: Arc<Metrics>,
 }
 
-pub async fn start_api(state: AppState, port: u16) {
-    let api = Router::new()
+pub async fn start_api(state: AppState, config: ApiConfig) {
+    let explorer = Router::new()
         .route("/balance/:address", get(get_balance))
         .route("/balances", get(get_balances))
-        .route("/wallet", get(load_wallet))
-        .route("/wallet/create", put(create_wallet))
-        .route("/tx", post(create_tx))
+        .route("/address/:address/txs", get(get_address_txs))
+        .route("/blocks", get(get_blocks))
+        .route("/block/:height", get(get_block))
+        .route("/block/hash/:hash", get(get_block_by_hash))
+        .route("/tip", get(get_tip))
+        .route("/tx/:txid", get(get_tx))
+        .route("/events/ws", get(events_ws))
+        .route("/events/sse", get(events_sse))
+        .route("/verify-message", post(verify_message))
+        .route("/tx/combine", post(combine_txs))
+        .route("/peers", get(get_peers))
+        .route("/chain", get(get_chain_info))
+        // права на отдельные методы проверяет сам обработчик
+        .route("/rpc", post(rpc::handle))
+        .route("/metrics", get(metrics::render))
         .route("/valid", get(valid_blockchain))
-        .with_state(state);
+        .route("/supply", get(get_supply))
+        .route_layer(middleware::from_fn(|request, next| auth::require(Role::Read, request, next)));
+
+    // майнинг блока и получение ключа из пароля занимают узел надолго
+    let expensive = || middleware::from_fn_with_state(state.clone(), limits::limit_concurrency);
+
+    let wallet = Router::new()
+        .route("/wallets", get(list_wallets))
+        .route("/wallets/:name/watch", post(watch_address))
+        .route("/wallets/:name/multisig/tx", post(create_multisig_tx))
+        .route("/wallets/:name/sign", post(sign_multisig_tx))
+        .route("/wallets/:name/unlock", post(unlock_wallet).layer(expensive()))
+        .route("/wallets/:name/lock", post(lock_wallet))
+        .route("/wallets/:name/accounts", post(add_wallet_account))
+        .route("/wallets/:name/accounts/:index", put(set_account_label))
+        .route("/wallet/sign-message", post(sign_message))
+        .route("/tx", post(create_tx).layer(expensive()))
+        .route("/tx/send", post(send_tx).layer(expensive()))
+        .route("/psbt/create", post(create_partial_tx))
+        .route("/psbt/send", post(send_partial_tx).layer(expensive()))
+        .route_layer(middleware::from_fn(|request, next| auth::require(Role::Wallet, request, next)));
+
+    let admin = Router::new()
+        .route("/wallets/:name", put(create_wallet).layer(expensive()))
+        .route("/wallets/:name/import", put(import_wallet_key).layer(expensive()))
+        .route("/wallets/:name/watch", put(create_watch_wallet))
+        .route("/wallets/:name/multisig", put(create_multisig_wallet))
+        .route("/wallets/:name/password", post(change_wallet_password).layer(expensive()))
+        .route("/wallets/:name/export", post(export_wallet_key).layer(expensive()))
+        .route_layer(middleware::from_fn(|request, next| auth::require(Role::Admin, request, next)));
+
+    let api = explorer
+        .merge(wallet)
+        .merge(admin)
+        .route_layer(middleware::from_fn_with_state(state.clone(), metrics::track_latency))
+        .layer(middleware::from_fn_with_state(state.clone(), limits::rate_limit))
+        .layer(middleware::from_fn_with_state(state.clone(), auth::authenticate))
+        .layer(DefaultBodyLimit::max(MAX_BODY_SIZE))
+        // язык выбирается раньше проверок, чтобы их отказы тоже были на языке запроса
+        .layer(middleware::from_fn(i18n::localize))
+        // предварительные запросы браузера проходят без ключа
+        .layer(cors_layer(&config.cors_origins))
+        .layer(trace_layer())
+        .with_state(state.clone());
+
+    // сессии разблокировки проверяются раз в секунду, чтобы ключи не задерживались в памяти,
+    // заодно забываются клиенты, давно не обращавшиеся к API
+    tokio::spawn(async move {
+        let mut interval = tokio::time::interval(Duration::from_secs(1));
+        loop {
+            interval.tick().await;
+            state.wallets.lock_expired();
+            state.limits.purge();
+        }
+    });
+
+    match config.tls {
+        None => {
+            info!(addr = %config.addr, tls = false, "API запущен");
+            let listener = tokio::net::TcpListener::bind(config.addr).await.unwrap();
+            axum::serve(listener, api.into_make_service_with_connect_info::<SocketAddr>())
+                .with_graceful_shutdown(shutdown_signal())
+                .await
+                .unwrap()
+        }
+        Some((cert, key)) => {
+            // провайдер мог быть уже установлен, тогда используется он
+            let _ = rustls::crypto::ring::default_provider().install_default();
+            let tls = RustlsConfig::from_pem_file(cert, key)
+                .await
+                .unwrap_or_else(|e| fail(Message::InvalidTls(e.to_string())));
+
+            let handle = Handle::new();
+            let shutdown = handle.clone();
+            tokio::spawn(async move {
+                shutdown_signal().await;
+                shutdown.graceful_shutdown(None);
+            });
+
+            info!(addr = %config.addr, tls = true, "API запущен");
+            axum_server::bind_rustls(config.addr, tls)
+                .handle(handle)
+                .serve(api.into_make_service_with_connect_info::<SocketAddr>())
+                .await
+                .unwrap()
+        }
+    }
+}
+
+// у каждого запроса свой span, в котором пишутся и записи обработчиков
+fn trace_layer() -> TraceLayer<SharedClassifier<ServerErrorsAsFailures>, impl MakeSpan<Body> + Clone> {
+    TraceLayer::new_for_http()
+        .make_span_with(|request: &axum::http::Request<Body>| {
+            let peer = request.extensions().get::<ConnectInfo<SocketAddr>>().map(|info| info.0.to_string());
+            info_span!("request", method = %request.method(), path = %request.uri().path(), peer = %peer.unwrap_or_default())
+        })
+        .on_response(DefaultOnResponse::new().level(Level::INFO))
+}
+
+fn cors_layer(origins: &[String]) -> CorsLayer {
+    let allow_origin = if origins.iter().any(|origin| origin == "*") {
+        AllowOrigin::any()
+    } else {
+        AllowOrigin::list(
+            origins
+                .iter()
+                .map(|origin| origin.parse().unwrap_or_else(|_| fail(Message::InvalidCorsOrigin(origin.clone())))),
+        )
+    };
+    CorsLayer::new()
+        .allow_origin(allow_origin)
+        .allow_methods([Method::GET, Method::POST, Method::PUT])
+        .allow_headers([
+            CONTENT_TYPE,
+            AUTHORIZATION,
+            HeaderName::from_static("x-api-key"),
+            HeaderName::from_static("x-api-timestamp"),
+            HeaderName::from_static("x-api-signature"),
+        ])
+}
 
-    let addr = format!("127.0.0.1:{}", port);
-    println!("API Gateway запушен на http://{}", addr);
+// операции ниже вызываются и из REST, и из JSON-RPC
+
+pub fn balance(state: &AppState, address: &str) -> Result<Balance, ApiError> {
+    let blockchain = state.blockchain.lock().unwrap();
+    if !address::is_valid(address, &blockchain.spec().address_prefix) {
+        return Err(ApiError::InvalidParams(Message::InvalidAddress));
+    }
+    Ok(blockchain.load_balance(address))
+}
 
-    let listener = tokio::net::TcpListener::bind(&addr).await.unwrap();
+pub fn block(state: &AppState, id: BlockId) -> Result<BlockInfo, ApiError> {
+    let blockchain = state.blockchain.lock().unwrap();
+    let block = match id {
+        BlockId::Height(height) => blockchain.load_block(height),
+        BlockId::Hash(hash) => blockchain.block_by_hash(&hash),
+    };
+    match block {
+        Some(block) => Ok(block_info(&blockchain, block, true)),
+        None => Err(ApiError::NotFound(Message::BlockNotFound)),
+    }
+}
 
-    axum::serve(listener, api.into_make_service())
-        .with_graceful_shutdown(shutdown_signal())
-        .await
-        .unwrap()
+// возвращает идентификатор принятой транзакции
+pub fn send_transaction(state: &AppState, tx: Transaction) -> Result<String, ApiError> {
+    let mut blockchain = state.blockchain.lock().unwrap();
+    submit_transaction(state, &mut blockchain, tx)
+}
+
+pub fn peers(state: &AppState) -> Vec<String> {
+    state.p2p.nodes.clone()
+}
+
+pub fn chain_info(state: &AppState) -> ChainInfo {
+    let blockchain = state.blockchain.lock().unwrap();
+    let tip = blockchain.latest_block().unwrap();
+    ChainInfo {
+        network_id: blockchain.spec().network_id.clone(),
+        genesis_hash: blockchain.genesis_hash(),
+        ledger: blockchain.spec().ledger,
+        difficulty: blockchain.spec().difficulty,
+        height: tip.index,
+        tip_hash: tip.hash,
+    }
 }
 
 async fn get_balance(Path(address): Path<String>, State(state): State<AppState>) -> impl IntoResponse {
-    let blockchain =  state.blockchain.lock().unwrap();
-    Json(blockchain.load_balance(&address))
+    balance(&state, &address).map(Json)
+}
+
+async fn get_peers(State(state): State<AppState>) -> impl IntoResponse {
+    Json(peers(&state))
+}
+
+async fn get_chain_info(State(state): State<AppState>) -> impl IntoResponse {
+    Json(chain_info(&state))
 }
 
 async fn get_balances(State(state): State<AppState>) -> impl IntoResponse {
@@ -61,45 +594,500 @@ async fn get_balances(State(state): State<AppState>) -> impl IntoResponse {
     Json(blockchain.load_balances())
 }
 
-async fn load_wallet(Json(access_data): Json<WalletAccessData>) -> impl IntoResponse {
-    let wallet = Wallet::load_from_file_encrypted(&access_data.file_name, &access_data.password);
-    Json(format!("Адрес кошелька: {}", wallet.address()))
+async fn get_address_txs(
+    Path(address): Path<String>,
+    Query(page): Query<PageParams>,
+    State(state): State<AppState>,
+) -> impl IntoResponse {
+    let blockchain = state.blockchain.lock().unwrap();
+    if !address::is_valid(&address, &blockchain.spec().address_prefix) {
+        return reply(StatusCode::BAD_REQUEST, Message::InvalidAddress);
+    }
+
+    let limit = page.limit.unwrap_or(DEFAULT_PAGE_LIMIT).min(MAX_PAGE_LIMIT);
+    let (total, history) = blockchain.address_history(&address, page.offset, limit);
+    let txs = history
+        .into_iter()
+        .map(|(location, transaction)| TxInfo {
+            confirmations: blockchain.confirmations(location.height),
+            location,
+            transaction,
+        })
+        .collect();
+    Json(AddressHistory { address, total, offset: page.offset, limit, txs }).into_response()
+}
+
+fn block_info(blockchain: &Blockchain, block: Block, with_transactions: bool) -> BlockInfo {
+    BlockInfo {
+        height: block.index,
+        confirmations: blockchain.confirmations(block.index),
+        txids: block.transactions.iter().map(|tx| tx.id()).collect(),
+        transactions: with_transactions.then_some(block.transactions),
+        hash: block.hash,
+        prev_hash: block.prev_hash,
+        timestamp: block.timestamp,
+        nonce: block.value,
+    }
+}
+
+async fn get_blocks(Query(page): Query<PageParams>, State(state): State<AppState>) -> impl IntoResponse {
+    let blockchain = state.blockchain.lock().unwrap();
+    let limit = page.limit.unwrap_or(DEFAULT_PAGE_LIMIT).min(MAX_PAGE_LIMIT);
+    let blocks = blockchain
+        .blocks(page.offset, limit)
+        .into_iter()
+        .map(|block| block_info(&blockchain, block, false))
+        .collect();
+    Json(BlockPage { total: blockchain.tip_height() + 1, offset: page.offset, limit, blocks })
+}
+
+async fn get_block(Path(height): Path<u64>, State(state): State<AppState>) -> impl IntoResponse {
+    block(&state, BlockId::Height(height)).map(Json)
+}
+
+async fn get_block_by_hash(Path(hash): Path<String>, State(state): State<AppState>) -> impl IntoResponse {
+    block(&state, BlockId::Hash(hash)).map(Json)
+}
+
+async fn get_tip(State(state): State<AppState>) -> impl IntoResponse {
+    let blockchain = state.blockchain.lock().unwrap();
+    Json(block_info(&blockchain, blockchain.latest_block().unwrap(), false))
+}
+
+async fn get_tx(Path(txid): Path<String>, State(state): State<AppState>) -> impl IntoResponse {
+    let blockchain = state.blockchain.lock().unwrap();
+    match blockchain.find_transaction(&txid) {
+        Some((location, transaction)) => Json(TxInfo {
+            confirmations: blockchain.confirmations(location.height),
+            location,
+            transaction,
+        })
+        .into_response(),
+        None => reply(StatusCode::NOT_FOUND, Message::TransactionNotFound),
+    }
+}
+
+// ошибка - первый некорректный адрес из фильтра
+fn event_addresses(state: &AppState, filter: EventFilter) -> Result<HashSet<String>, String> {
+    let prefix = address_prefix(state);
+    let addresses: HashSet<String> = filter
+        .addresses
+        .iter()
+        .flat_map(|addresses| addresses.split(','))
+        .filter(|address| !address.is_empty())
+        .map(|address| address.to_string())
+        .collect();
+    if let Some(address) = addresses.iter().find(|address| !address::is_valid(address, &prefix)) {
+        return Err(address.clone());
+    }
+    Ok(addresses)
+}
+
+async fn events_ws(
+    ws: WebSocketUpgrade,
+    Query(filter): Query<EventFilter>,
+    State(state): State<AppState>,
+) -> impl IntoResponse {
+    let addresses = match event_addresses(&state, filter) {
+        Ok(addresses) => addresses,
+        Err(address) => {
+            return reply(StatusCode::BAD_REQUEST, Message::InvalidAddressValue(address));
+        }
+    };
+    let receiver = state.blockchain.lock().unwrap().subscribe();
+    ws.on_upgrade(move |socket| send_events(socket, receiver, addresses))
+}
+
+async fn send_events(mut socket: WebSocket, mut receiver: Receiver<Event>, addresses: HashSet<String>) {
+    loop {
+        tokio::select! {
+            event = receiver.recv() => match event {
+                Ok(event) if event.concerns(&addresses) => {
+                    let text = serde_json::to_string(&event).unwrap();
+                    if socket.send(ws::Message::Text(text)).await.is_err() {
+                        break;
+                    }
+                }
+                Ok(_) | Err(RecvError::Lagged(_)) => {}
+                Err(RecvError::Closed) => break,
+            },
+            // входящие сообщения не нужны, чтение только замечает закрытие соединения
+            message = socket.recv() => match message {
+                Some(Ok(ws::Message::Close(_))) | Some(Err(_)) | None => break,
+                Some(Ok(_)) => {}
+            },
+        }
+    }
+}
+
+async fn events_sse(Query(filter): Query<EventFilter>, State(state): State<AppState>) -> impl IntoResponse {
+    let addresses = match event_addresses(&state, filter) {
+        Ok(addresses) => addresses,
+        Err(address) => {
+            return reply(StatusCode::BAD_REQUEST, Message::InvalidAddressValue(address));
+        }
+    };
+    let receiver = state.blockchain.lock().unwrap().subscribe();
+    // отставший подписчик пропускает вытесненные события
+    let stream = BroadcastStream::new(receiver).filter_map(move |event| {
+        let event = event.ok().filter(|event| event.concerns(&addresses))?;
+        let sse_event = sse::Event::default().event(event.name()).json_data(&event).unwrap();
+        Some(Ok::<_, Infallible>(sse_event))
+    });
+    Sse::new(stream).keep_alive(KeepAlive::default()).into_response()
+}
+
+fn address_prefix(state: &AppState) -> String {
+    state.blockchain.lock().unwrap().spec().address_prefix.clone()
+}
+
+fn wallet_error(e: WalletError) -> Response {
+    let status = match e {
+        WalletError::InvalidName
+        | WalletError::InvalidMnemonic(_)
+        | WalletError::InvalidKey
+        | WalletError::SingleAccount
+        | WalletError::NoPrivateKeys
+        | WalletError::NotWatchOnly
+        | WalletError::InvalidWatchTarget
+        | WalletError::InvalidPublicKey
+        | WalletError::InvalidMultisig
+        | WalletError::NotMultisig
+        | WalletError::NotMultisigTransaction
+        | WalletError::KeyNotInMultisig => StatusCode::BAD_REQUEST,
+        WalletError::NotFound | WalletError::UnknownAccount(_) => StatusCode::NOT_FOUND,
+        WalletError::AlreadyExists | WalletError::DuplicateAddress => StatusCode::CONFLICT,
+        WalletError::WrongPassword => StatusCode::UNAUTHORIZED,
+        WalletError::Locked => StatusCode::FORBIDDEN,
+        // поврежденный файл в каталоге узла - ошибка сервера, а не запроса
+        WalletError::UnreadableFile(_)
+        | WalletError::InvalidFile(_)
+        | WalletError::UnsupportedVersion(_)
+        | WalletError::WriteFailed(_) => {
+            StatusCode::INTERNAL_SERVER_ERROR
+        }
+    };
+    reply(status, e)
+}
+
+// операции с паролем заняты KDF, поэтому выполняются в отдельном потоке и не задерживают остальные запросы
+async fn with_wallets<T: Send + 'static>(
+    state: &AppState,
+    operation: impl FnOnce(&WalletManager) -> T + Send + 'static,
+) -> T {
+    let wallets = state.wallets.clone();
+    tokio::task::spawn_blocking(move || operation(&wallets)).await.unwrap()
+}
+
+async fn list_wallets(State(state): State<AppState>) -> impl IntoResponse {
+    match state.wallets.list() {
+        Ok(wallets) => Json(wallets).into_response(),
+        Err(e) => wallet_error(e),
+    }
+}
+
+async fn create_wallet(
+    Path(name): Path<String>,
+    State(state): State<AppState>,
+    Json(create_data): Json<CreateWalletData>,
+) -> impl IntoResponse {
+    let kdf = Kdf::recommended(create_data.kdf);
+    let created = with_wallets(&state, move |wallets| {
+        wallets.create(&name, &create_data.password, create_data.mnemonic.as_deref(), kdf)
+    });
+    match created.await {
+        Ok((mnemonic, account)) => Json(WalletCreated { mnemonic, address: account.address }).into_response(),
+        Err(e) => wallet_error(e),
+    }
+}
+
+async fn import_wallet_key(
+    Path(name): Path<String>,
+    State(state): State<AppState>,
+    Json(import_data): Json<ImportKeyData>,
+) -> impl IntoResponse {
+    let kdf = Kdf::recommended(import_data.kdf);
+    let imported = with_wallets(&state, move |wallets| {
+        wallets.import_key(&name, &import_data.password, &import_data.private_key, kdf)
+    });
+    match imported.await {
+        Ok(account) => Json(WalletCreated { mnemonic: None, address: account.address }).into_response(),
+        Err(e) => wallet_error(e),
+    }
+}
+
+async fn create_watch_wallet(Path(name): Path<String>, State(state): State<AppState>) -> impl IntoResponse {
+    match state.wallets.create_watch_only(&name) {
+        Ok(()) => reply(StatusCode::OK, Message::WatchWalletCreated),
+        Err(e) => wallet_error(e),
+    }
+}
+
+async fn watch_address(
+    Path(name): Path<String>,
+    State(state): State<AppState>,
+    Json(watch_data): Json<WatchAddressData>,
+) -> impl IntoResponse {
+    match state.wallets.watch(&name, &watch_data.address, watch_data.label) {
+        Ok(account) => Json(account).into_response(),
+        Err(e) => wallet_error(e),
+    }
+}
+
+async fn create_multisig_wallet(
+    Path(name): Path<String>,
+    State(state): State<AppState>,
+    Json(multisig_data): Json<MultisigWalletData>,
+) -> impl IntoResponse {
+    match state.wallets.create_multisig(&name, multisig_data.threshold, &multisig_data.public_keys) {
+        Ok(account) => Json(account).into_response(),
+        Err(e) => wallet_error(e),
+    }
+}
+
+// неподписанная транзакция с мультиподписного адреса, ее передают участникам на подпись
+async fn create_multisig_tx(
+    Path(name): Path<String>,
+    State(state): State<AppState>,
+    Json(tx): Json<MultisigTxData>,
+) -> impl IntoResponse {
+    let prefix = address_prefix(&state);
+    if !address::is_valid(&tx.to, &prefix) {
+        return reply(StatusCode::BAD_REQUEST, Message::InvalidRecipient);
+    }
+
+    let multisig = match state.wallets.multisig(&name) {
+        Ok(multisig) => multisig,
+        Err(e) => return wallet_error(e),
+    };
+    let blockchain = state.blockchain.lock().unwrap();
+    let mut new_tx = match blockchain.prepare_transaction(&multisig.address(&prefix), &tx.to, tx.amount) {
+        Ok(new_tx) => new_tx,
+        Err(e) => {
+            return reply(StatusCode::BAD_REQUEST, Message::TransactionRejected(e));
+        }
+    };
+    new_tx.signature_type = tx.signature_type;
+    new_tx.multisig = Some(multisig);
+    Json(new_tx).into_response()
+}
+
+async fn sign_multisig_tx(
+    Path(name): Path<String>,
+    State(state): State<AppState>,
+    Json(sign_data): Json<SignTransactionData>,
+) -> impl IntoResponse {
+    let mut tx = sign_data.transaction;
+    match state.wallets.sign_multisig(&name, sign_data.account, &mut tx) {
+        Ok(()) => Json(tx).into_response(),
+        Err(e) => wallet_error(e),
+    }
+}
+
+async fn unlock_wallet(
+    Path(name): Path<String>,
+    State(state): State<AppState>,
+    Json(unlock_data): Json<UnlockWalletData>,
+) -> impl IntoResponse {
+    let timeout = unlock_data.timeout.map(Duration::from_secs).unwrap_or(DEFAULT_UNLOCK_TIMEOUT);
+    match with_wallets(&state, move |wallets| wallets.unlock(&name, &unlock_data.password, timeout)).await {
+        Ok(timeout) => reply(StatusCode::OK, Message::WalletUnlocked(timeout.as_secs())),
+        Err(e) => wallet_error(e),
+    }
+}
+
+async fn lock_wallet(Path(name): Path<String>, State(state): State<AppState>) -> impl IntoResponse {
+    match state.wallets.lock(&name) {
+        Ok(()) => reply(StatusCode::OK, Message::WalletLocked),
+        Err(e) => wallet_error(e),
+    }
+}
+
+async fn add_wallet_account(
+    Path(name): Path<String>,
+    State(state): State<AppState>,
+    Json(label_data): Json<AccountLabelData>,
+) -> impl IntoResponse {
+    match state.wallets.add_account(&name, label_data.label) {
+        Ok(account) => Json(account).into_response(),
+        Err(e) => wallet_error(e),
+    }
+}
+
+async fn set_account_label(
+    Path((name, index)): Path<(String, u32)>,
+    State(state): State<AppState>,
+    Json(label_data): Json<AccountLabelData>,
+) -> impl IntoResponse {
+    match state.wallets.set_label(&name, index, label_data.label) {
+        Ok(account) => Json(account).into_response(),
+        Err(e) => wallet_error(e),
+    }
+}
+
+async fn change_wallet_password(
+    Path(name): Path<String>,
+    State(state): State<AppState>,
+    Json(password_data): Json<ChangePasswordData>,
+) -> impl IntoResponse {
+    let kdf = Kdf::recommended(password_data.kdf);
+    let changed = with_wallets(&state, move |wallets| {
+        wallets.change_password(&name, &password_data.password, &password_data.new_password, kdf)
+    });
+    match changed.await {
+        Ok(()) => reply(StatusCode::OK, Message::PasswordChanged),
+        Err(e) => wallet_error(e),
+    }
+}
+
+async fn export_wallet_key(
+    Path(name): Path<String>,
+    State(state): State<AppState>,
+    Json(export_data): Json<ExportKeyData>,
+) -> impl IntoResponse {
+    let exported = with_wallets(&state, move |wallets| {
+        wallets.export_key(&name, &export_data.password, export_data.account, export_data.format)
+    });
+    match exported.await {
+        Ok(key) => Json(key).into_response(),
+        Err(e) => wallet_error(e),
+    }
+}
+
+async fn sign_message(State(state): State<AppState>, Json(sign_data): Json<SignMessageData>) -> impl IntoResponse {
+    let wallet = match state.wallets.signer(&sign_data.wallet, sign_data.account) {
+        Ok(wallet) => wallet,
+        Err(e) => return wallet_error(e),
+    };
+    let blockchain = state.blockchain.lock().unwrap();
+    let spec = blockchain.spec();
+    Json(wallet.sign_message(&sign_data.message, &spec.network_id, &spec.address_prefix, sign_data.signature_type))
+        .into_response()
 }
 
-async fn create_wallet(Json(access_data): Json<WalletAccessData>) -> impl IntoResponse {
-    let wallet = Wallet::new();
-    wallet.save_to_file_encrypted(&access_data.file_name, &access_data.password);
-    Json(format!("Адрес кошелька {}", wallet.address()))
+async fn verify_message(State(state): State<AppState>, Json(signed): Json<SignedMessage>) -> impl IntoResponse {
+    let blockchain = state.blockchain.lock().unwrap();
+    let spec = blockchain.spec();
+    Json(signed.verify(&spec.network_id, &spec.address_prefix))
 }
 
 async fn create_tx(State(state): State<AppState>, Json(tx): Json<NewTransactionData>) -> impl IntoResponse {
-    let wallet = Wallet::load_from_file_encrypted(&tx.from_file, &tx.password);
-    let from_address = wallet.address();
-
-    let transaction_data = format!("{}{}{}", from_address, tx.to, tx.amount);
-    let signature = wallet.sign(transaction_data.as_bytes());
-
-    let new_tx = Transaction {
-        from: from_address.clone(),
-        to: tx.to.clone(),
-        amount: tx.amount,
-        signature,
-        public_key: wallet.public_key.to_sec1_bytes().to_vec(),
+    let prefix = address_prefix(&state);
+    if !address::is_valid(&tx.to, &prefix) {
+        return reply(StatusCode::BAD_REQUEST, Message::InvalidRecipient);
+    }
+
+    let wallet = match state.wallets.signer(&tx.wallet, tx.account) {
+        Ok(wallet) => wallet,
+        Err(e) => return wallet_error(e),
     };
+    let from_address = wallet.address(&prefix);
 
     let mut blockchain = state.blockchain.lock().unwrap();
-    blockchain.add_block(&from_address, vec![new_tx]);
+    let mut new_tx = match blockchain.prepare_transaction(&from_address, &tx.to, tx.amount) {
+        Ok(new_tx) => new_tx,
+        Err(e) => {
+            return reply(StatusCode::BAD_REQUEST, Message::TransactionRejected(e));
+        }
+    };
+    new_tx.signature_type = tx.signature_type;
+    new_tx.public_key = wallet.public_key.to_sec1_bytes().to_vec();
+    new_tx.signature = wallet.sign(new_tx.signing_data().as_bytes(), tx.signature_type);
+
+    transaction_added(submit_transaction(&state, &mut blockchain, new_tx))
+}
+
+// подписи от разных участников мультиподписи собираются в одну транзакцию
+async fn combine_txs(Json(combine_data): Json<CombineTransactionsData>) -> impl IntoResponse {
+    let mut transactions = combine_data.transactions.into_iter();
+    let Some(mut combined) = transactions.next() else {
+        return reply(StatusCode::BAD_REQUEST, Message::NothingToCombine);
+    };
+    let signing_data = combined.signing_data();
+    let Some(multisig) = combined.multisig.as_mut() else {
+        return reply(StatusCode::BAD_REQUEST, WalletError::NotMultisigTransaction);
+    };
+
+    for tx in transactions {
+        let merged = tx.signing_data() == signing_data
+            && tx.multisig.as_ref().is_some_and(|other| multisig.merge(other));
+        if !merged {
+            return reply(StatusCode::BAD_REQUEST, Message::TransactionsMismatch);
+        }
+    }
+    Json(combined).into_response()
+}
+
+// отправка транзакции, подписанной вне узла
+async fn send_tx(State(state): State<AppState>, Json(send_data): Json<SendTransactionData>) -> impl IntoResponse {
+    transaction_added(send_transaction(&state, send_data.transaction))
+}
 
-    let block = blockchain.latest_block().unwrap();
+// транзакция для подписи вне узла; номер, комиссия и входы берутся из состояния цепочки
+async fn create_partial_tx(State(state): State<AppState>, Json(tx): Json<PartialTxData>) -> impl IntoResponse {
+    let prefix = address_prefix(&state);
+    if !address::is_valid(&tx.to, &prefix) {
+        return reply(StatusCode::BAD_REQUEST, Message::InvalidRecipient);
+    }
+    let Some((version, _)) = address::decode(&tx.from, &prefix) else {
+        return reply(StatusCode::BAD_REQUEST, Message::InvalidSender);
+    };
+    let multisig = if version == address::MULTISIG_VERSION {
+        match state.wallets.find_multisig(&tx.from) {
+            Ok(Some(multisig)) => Some(multisig),
+            Ok(None) => return reply(StatusCode::BAD_REQUEST, Message::MultisigNotFound),
+            Err(e) => return wallet_error(e),
+        }
+    } else {
+        None
+    };
 
-    let message = Message {
-        command: "block".to_string(),
-        payload: bincode::serialize(&block).unwrap()
+    let blockchain = state.blockchain.lock().unwrap();
+    let mut new_tx = match blockchain.prepare_transaction(&tx.from, &tx.to, tx.amount) {
+        Ok(new_tx) => new_tx,
+        Err(e) => {
+            return reply(StatusCode::BAD_REQUEST, Message::TransactionRejected(e));
+        }
     };
+    new_tx.signature_type = tx.signature_type;
+    new_tx.multisig = multisig;
+    let spent_outputs = new_tx
+        .inputs
+        .iter()
+        .map(|input| blockchain.load_utxo(input).unwrap().output)
+        .collect();
+    Json(PartialTransaction::new(blockchain.spec(), &blockchain.genesis_hash(), new_tx, spent_outputs)).into_response()
+}
+
+async fn send_partial_tx(State(state): State<AppState>, Json(partial): Json<PartialTransaction>) -> impl IntoResponse {
+    let mut blockchain = state.blockchain.lock().unwrap();
+    let checked = partial
+        .check_version()
+        .and_then(|_| partial.check_network(&blockchain.spec().network_id, &blockchain.genesis_hash()))
+        .and_then(|_| if partial.is_complete() { Ok(()) } else { Err(PartialTxError::Incomplete) });
+    if let Err(e) = checked {
+        return reply(StatusCode::BAD_REQUEST, e);
+    }
+    transaction_added(submit_transaction(&state, &mut blockchain, partial.transaction))
+}
+
+// транзакция сразу включается в новый блок, награду за который получает отправитель
+fn submit_transaction(state: &AppState, blockchain: &mut Blockchain, tx: Transaction) -> Result<String, ApiError> {
+    let miner_address = tx.from.clone();
+    let txid = tx.id();
+    let block = blockchain
+        .add_block(&miner_address, vec![tx])
+        .map_err(|e| ApiError::Rejected(Message::TransactionRejected(e)))?;
+
+    let message = state.p2p.message("block", bincode::serialize(&block).unwrap());
 
     state.p2p.broadcast(&message);
 
-    Json("Транзакция успешно добавлена".to_string())
+    Ok(txid)
+}
+
+fn transaction_added(result: Result<String, ApiError>) -> Response {
+    result.map(|_| reply(StatusCode::OK, Message::TransactionAdded)).into_response()
 }
 
 async fn valid_blockchain(State(state): State<AppState>) -> impl IntoResponse {
@@ -107,9 +1095,20 @@ async fn valid_blockchain(State(state): State<AppState>) -> impl IntoResponse {
     Json(blockchain.is_valid())
 }
 
+async fn get_supply(State(state): State<AppState>) -> impl IntoResponse {
+    let blockchain = state.blockchain.lock().unwrap();
+    let height = blockchain.latest_block().unwrap().index;
+    Json(SupplyInfo {
+        height,
+        circulating_supply: blockchain.circulating_supply(),
+        max_supply: blockchain.spec().max_supply,
+        next_block_subsidy: blockchain.spec().subsidy_at(height + 1),
+    })
+}
+
 async fn shutdown_signal() {
     if let Err(e) = signal::ctrl_c().await {
-        eprintln!("Не удалось обработать CTRL+C: {}", e);
+        error!(error = %e, "не удалось обработать Ctrl+C");
     }
-    println!("Получен Ctrl+C. Завершаем приложение.")
+    info!("получен Ctrl+C, узел завершает работу")
 }
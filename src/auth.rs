@@ -0,0 +1,175 @@
+// доступ к API по ключам из файла, заданного при запуске узла.
+// ключ передается как Authorization: Bearer <секрет> или подписью HMAC-SHA256 запроса
+// в заголовках X-Api-Key, X-Api-Timestamp и X-Api-Signature, тогда секрет по сети не передается.
+// подписывается строка "{время в мс}\n{метод}\n{путь с параметрами}\n{sha256 тела в hex}"
+
+use crate::api::{reply, AppState};
+use crate::i18n::{fail, Message};
+use crate::limits::MAX_BODY_SIZE;
+use crate::utils::now;
+use axum::body::{to_bytes, Body};
+use axum::extract::{ConnectInfo, Request, State};
+use axum::http::header::{AUTHORIZATION, WWW_AUTHENTICATE};
+use axum::http::{HeaderMap, StatusCode};
+use axum::middleware::Next;
+use axum::response::Response;
+use hmac::{Hmac, Mac};
+use serde::Deserialize;
+use sha2::{Digest, Sha256};
+use std::fs::read_to_string;
+use std::net::SocketAddr;
+use std::sync::atomic::Ordering;
+
+type HmacSha256 = Hmac<Sha256>;
+
+// насколько время подписанного запроса может отличаться от времени узла
+const MAX_CLOCK_SKEW_MS: u128 = 5 * 60 * 1000;
+
+// роли упорядочены: каждая следующая разрешает все, что разрешают предыдущие
+#[derive(Clone, Copy, Debug, PartialEq, Eq, PartialOrd, Ord, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum Role {
+    // обозреватель цепочки
+    Read,
+    // работа с существующими кошельками и отправка транзакций
+    Wallet,
+    // создание, импорт и экспорт кошельков
+    Admin,
+}
+
+// ключ, с которым пришел запрос; кладется в расширения запроса рядом с ролью
+#[derive(Clone)]
+pub struct ApiKeyId(pub String);
+
+#[derive(Deserialize)]
+struct ApiKey {
+    id: String,
+    secret: String,
+    role: Role,
+}
+
+#[derive(Deserialize)]
+struct ApiKeysFile {
+    // роль запросов без ключа; если не задана, такие запросы отклоняются
+    anonymous_role: Option<Role>,
+    #[serde(default)]
+    keys: Vec<ApiKey>,
+}
+
+pub struct ApiAuth {
+    anonymous_role: Option<Role>,
+    keys: Vec<ApiKey>,
+    // хеши секретов для сравнения bearer-токенов
+    secret_hashes: Vec<[u8; 32]>,
+}
+
+impl Default for ApiAuth {
+    // без файла ключей API доступен только на чтение
+    fn default() -> Self {
+        ApiAuth { anonymous_role: Some(Role::Read), keys: vec![], secret_hashes: vec![] }
+    }
+}
+
+impl ApiAuth {
+    pub fn load(path: &str) -> Self {
+        let content = read_to_string(path).unwrap_or_else(|e| fail(Message::OpenFailed(path.to_string(), e.to_string())));
+        let file: ApiKeysFile =
+            serde_json::from_str(&content).unwrap_or_else(|e| fail(Message::InvalidApiKeysFile(e.to_string())));
+        let secret_hashes = file.keys.iter().map(|key| Sha256::digest(key.secret.as_bytes()).into()).collect();
+        ApiAuth { anonymous_role: file.anonymous_role, keys: file.keys, secret_hashes }
+    }
+
+    pub fn load_or_default(path: Option<String>) -> Self {
+        path.as_deref().map(ApiAuth::load).unwrap_or_default()
+    }
+
+    fn bearer_key(&self, token: &str) -> Option<&ApiKey> {
+        let hash: [u8; 32] = Sha256::digest(token.as_bytes()).into();
+        let position = self.secret_hashes.iter().position(|secret_hash| *secret_hash == hash)?;
+        Some(&self.keys[position])
+    }
+
+    fn signed_key(&self, headers: &HeaderMap, method: &str, path: &str, body: &[u8]) -> Option<&ApiKey> {
+        let header = |name: &str| headers.get(name).and_then(|value| value.to_str().ok());
+        let key = self.keys.iter().find(|key| Some(key.id.as_str()) == header("x-api-key"))?;
+        let timestamp = header("x-api-timestamp")?;
+        let signature = hex::decode(header("x-api-signature")?).ok()?;
+
+        let time: u128 = timestamp.parse().ok()?;
+        if time.abs_diff(now()) > MAX_CLOCK_SKEW_MS {
+            return None;
+        }
+
+        let mut mac = HmacSha256::new_from_slice(key.secret.as_bytes()).unwrap();
+        let body_hash = hex::encode(Sha256::digest(body));
+        mac.update(format!("{}\n{}\n{}\n{}", timestamp, method, path, body_hash).as_bytes());
+        mac.verify_slice(&signature).ok()?;
+        Some(key)
+    }
+}
+
+fn unauthorized(state: &AppState, peer: SocketAddr) -> Response {
+    if let Some(response) = state.limits.charge_failed_auth(peer) {
+        return response;
+    }
+    let mut response = reply(StatusCode::UNAUTHORIZED, Message::Unauthorized);
+    response.headers_mut().insert(WWW_AUTHENTICATE, "Bearer".parse().unwrap());
+    response
+}
+
+// определяет роль запроса и кладет ее в расширения запроса
+pub async fn authenticate(
+    State(state): State<AppState>,
+    ConnectInfo(peer): ConnectInfo<SocketAddr>,
+    request: Request,
+    next: Next,
+) -> Response {
+    let auth = &state.auth;
+    let bearer = request
+        .headers()
+        .get(AUTHORIZATION)
+        .and_then(|value| value.to_str().ok())
+        .and_then(|value| value.strip_prefix("Bearer "))
+        .map(|token| token.to_string());
+
+    let (key, mut request) = if let Some(token) = bearer {
+        let Some(key) = auth.bearer_key(&token) else {
+            return unauthorized(&state, peer);
+        };
+        (Some(key), request)
+    } else if request.headers().contains_key("x-api-signature") {
+        let (parts, body) = request.into_parts();
+        let Ok(body) = to_bytes(body, MAX_BODY_SIZE).await else {
+            state.limits.rejected_body_size.fetch_add(1, Ordering::Relaxed);
+            return reply(StatusCode::PAYLOAD_TOO_LARGE, Message::PayloadTooLarge);
+        };
+        let path = parts.uri.path_and_query().map(|path| path.as_str()).unwrap_or("/");
+        let Some(key) = auth.signed_key(&parts.headers, parts.method.as_str(), path, &body) else {
+            return unauthorized(&state, peer);
+        };
+        (Some(key), Request::from_parts(parts, Body::from(body)))
+    } else {
+        (None, request)
+    };
+
+    let Some(role) = key.map(|key| key.role).or(auth.anonymous_role) else {
+        return unauthorized(&state, peer);
+    };
+    request.extensions_mut().insert(role);
+    if let Some(key) = key {
+        request.extensions_mut().insert(ApiKeyId(key.id.clone()));
+    }
+    next.run(request).await
+}
+
+fn forbidden() -> Response {
+    reply(StatusCode::FORBIDDEN, Message::Forbidden)
+}
+
+// слой для группы маршрутов, доступных начиная с роли required
+pub async fn require(required: Role, request: Request, next: Next) -> Response {
+    match request.extensions().get::<Role>() {
+        Some(role) if *role >= required => next.run(request).await,
+        _ => forbidden(),
+    }
+}
@@ -48,6 +48,26 @@ impl Block {
         }
     }
 
+    // блок с заранее подобранным значением, без майнинга
+    pub fn with_value(
+        index: u64,
+        timestamp: u128,
+        transactions: Vec<Transaction>,
+        prev_hash: String,
+        value: u64,
+    ) -> Self {
+        let mut block = Block {
+            index,
+            timestamp,
+            transactions,
+            prev_hash,
+            hash: String::new(),
+            value,
+        };
+        block.hash = block.calculate_hash();
+        block
+    }
+
     pub fn calculate_hash(&self) -> String {
         calculate_hash(format!(
             "{}{}{:?}{}{}",
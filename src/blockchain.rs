@@ -1,56 +1,186 @@
+use crate::address;
 use crate::block::Block;
-use crate::transaction::Transaction;
+use crate::chain_spec::ChainSpec;
+use crate::events::{Event, EventBus};
+use crate::i18n::{self, fail, Localize, Message};
+use crate::transaction::{OutPoint, Transaction, TxOutput};
 use crate::utils::now;
-use crate::wallet::Wallet;
+use crate::wallet::{SignatureScheme, Wallet};
+use serde::{Deserialize, Serialize};
+use std::collections::{HashMap, HashSet};
+use std::fmt::{Display, Formatter};
+use tokio::sync::broadcast::Receiver;
+use tracing::{field, info, info_span, warn};
 
 pub struct Blockchain {
     db: sled::Db,
-    difficulty: usize,
+    spec: ChainSpec,
+    events: EventBus,
 }
 
+// способ учета средств задается спецификацией цепочки и хранится в базе
+#[derive(Clone, Copy, Debug, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum Ledger {
+    // балансы адресов под префиксом balance:
+    Account,
+    // непотраченные выходы транзакций под префиксом utxo:
+    Utxo,
+}
+
+#[derive(Clone, Copy, Debug, Default, Serialize)]
+pub struct Balance {
+    pub mature: f64,
+    // награды майнеру, которые еще нельзя тратить
+    pub immature: f64,
+}
+
+// непотраченный выход вместе с высотой блока, в котором он создан
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct UtxoEntry {
+    pub output: TxOutput,
+    pub height: u64,
+    pub coinbase: bool,
+}
+
+// место транзакции в цепочке, хранится в индексах адресов и транзакций
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct TxLocation {
+    pub txid: String,
+    pub height: u64,
+    pub block_hash: String,
+    // номер транзакции в блоке
+    pub position: u32,
+}
+
+const ADDRESS_TX_PREFIX: &str = "addrtx:";
+const BLOCK_HASH_PREFIX: &str = "blockhash:";
+const TXID_PREFIX: &str = "txid:";
+const TIP_KEY: &str = "meta:tip";
+const INDEX_VERSION_KEY: &str = "meta:index_version";
+// увеличивается при появлении новых индексов, чтобы старые базы переиндексировались при запуске
+const INDEX_VERSION: u8 = 1;
 const BALANCE_PREFIX: &str = "balance:";
+const NONCE_PREFIX: &str = "nonce:";
 const BLOCK_PREFIX: &str = "block:";
-const NETWORK_ADDRESS: &str = "network";
+const UTXO_PREFIX: &str = "utxo:";
+const LEDGER_KEY: &str = "meta:ledger";
 
-const BLOCK_REWARD: f64 = 5.0;
-pub const TRANSACTION_FEE: f64 = 0.01;
+// насколько блок от другого узла может опережать локальные часы, мс
+const MAX_FUTURE_BLOCK_TIME: u128 = 2 * 60 * 60 * 1000;
+
+// допуск при сравнении сумм входов и выходов
+const AMOUNT_EPSILON: f64 = 1e-9;
+
+#[derive(Debug)]
+pub enum BlockError {
+    InvalidIndex,
+    InvalidPrevHash,
+    InvalidHash,
+    InsufficientWork,
+    InvalidTimestamp,
+    MissingCoinbase,
+    UnexpectedCoinbase,
+    InvalidReward,
+    InvalidAmount,
+    InvalidAddress,
+    InsufficientFee,
+    InvalidSignature,
+    DuplicateTransaction,
+    InsufficientFunds,
+    LedgerMismatch,
+    UnknownInput,
+    DoubleSpend,
+    InvalidFee,
+    ImmatureCoinbase,
+    InvalidNonce,
+}
+
+impl Display for BlockError {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{}", self.text(i18n::lang()))
+    }
+}
 
 // todo
 //  - транзакция попадает в пул, откуда ее может взять в блок майнер с кастомной комиссией
 
 
 impl Blockchain {
-    pub fn new(path: &str, difficulty: usize) -> Self {
-        let db = sled::open(path).expect("Failed to open database");
-        let blockchain = Blockchain { db, difficulty };
-
-        if blockchain.latest_block().is_none() {
-            println!("Инициализация первого блока...");
-            let initial_tx = Transaction {
-                from: NETWORK_ADDRESS.to_string(),
-                to: "02c9cfea78bd540fae61e64ba3b848f691aeabbd2a36b02a7dd57513752441523b".to_string(),
-                amount: 100.0,
-                signature: vec![],
-                public_key: vec![],
-            };
-            let initial_block = Block::new(
-                0,
-                now(),
-                vec![initial_tx.clone()],
-                "0".to_owned(),
-                difficulty,
-            );
-            let serialized_block = bincode::serialize(&initial_block).unwrap();
-            let key = format!("{}{}", BLOCK_PREFIX, initial_block.index);
-            blockchain.db.insert(key, serialized_block).unwrap();
-            blockchain.db.flush().unwrap();
+    pub fn new(path: &str, spec: ChainSpec) -> Self {
+        let db = sled::open(path).unwrap_or_else(|e| fail(Message::DatabaseOpenFailed(path.to_string(), e.to_string())));
+
+        match db.get(LEDGER_KEY).unwrap() {
+            Some(stored) => {
+                let stored: Ledger = bincode::deserialize(&stored).unwrap();
+                if stored != spec.ledger {
+                    fail(Message::LedgerMismatch(format!("{:?}", stored), format!("{:?}", spec.ledger)));
+                }
+            }
+            // режим учета записывается при создании цепочки, его нет только в базах первой версии узла,
+            // где у транзакций не было номеров, входов и выходов; такие блоки не читаются
+            None if db.scan_prefix(BLOCK_PREFIX).next().is_some() => {
+                fail(Message::IncompatibleDatabase(path.to_string()));
+            }
+            None => {
+                db.insert(LEDGER_KEY, bincode::serialize(&spec.ledger).unwrap()).unwrap();
+            }
+        }
+
+        let initial_block = spec.genesis_block();
+        if !initial_block.hash.starts_with(&"0".repeat(spec.difficulty)) {
+            fail(Message::GenesisNotMined);
+        }
 
-            blockchain.save_balance(&initial_tx.to, initial_tx.amount);
+        let blockchain = Blockchain { db, spec, events: EventBus::default() };
+
+        match blockchain.load_block(0) {
+            Some(stored) if stored.hash != initial_block.hash => {
+                fail(Message::GenesisMismatch(stored.hash, initial_block.hash));
+            }
+            Some(_) => {}
+            None => {
+                info!(hash = %initial_block.hash, "инициализация первого блока");
+                blockchain.save_block(&initial_block);
+            }
+        }
+
+        // базы, созданные до появления индексов, индексируются при запуске
+        if blockchain.db.get(INDEX_VERSION_KEY).unwrap().as_deref() != Some(&[INDEX_VERSION]) {
+            info!(version = INDEX_VERSION, "построение индексов");
+            for block in blockchain.load_blockchain() {
+                blockchain.index_block(&block);
+            }
+            blockchain.db.insert(INDEX_VERSION_KEY, vec![INDEX_VERSION]).unwrap();
+            blockchain.db.flush().unwrap();
         }
 
         blockchain
     }
 
+    pub fn spec(&self) -> &ChainSpec {
+        &self.spec
+    }
+
+    pub fn db_size(&self) -> u64 {
+        self.db.size_on_disk().unwrap()
+    }
+
+    pub fn subscribe(&self) -> Receiver<Event> {
+        self.events.subscribe()
+    }
+
+    pub fn genesis_hash(&self) -> String {
+        self.load_block(0).unwrap().hash
+    }
+
+    pub fn load_block(&self, index: u64) -> Option<Block> {
+        self.db
+            .get(format!("{}{}", BLOCK_PREFIX, index))
+            .unwrap()
+            .map(|val| bincode::deserialize(&val).unwrap())
+    }
+
     pub fn load_blockchain(&self) -> Vec<Block> {
         let mut blocks = vec![];
         for block_result in self.db.scan_prefix(BLOCK_PREFIX) {
@@ -63,105 +193,492 @@ impl Blockchain {
     }
 
     pub fn latest_block(&self) -> Option<Block> {
-        self.load_blockchain().last().cloned()
+        self.load_block(self.tip_height())
     }
 
-    pub fn add_block(&mut self, miner_address: &str, transactions: Vec<Transaction>) {
-        for tx in &transactions {
-            if tx.from != NETWORK_ADDRESS {
+    pub fn tip_height(&self) -> u64 {
+        self.db
+            .get(TIP_KEY)
+            .unwrap()
+            .map(|val| bincode::deserialize(&val).unwrap())
+            .unwrap_or_default()
+    }
 
-                let transaction_data = format!("{}{}{}", tx.from, tx.to, tx.amount);
-                if !Wallet::verify(&tx.public_key, transaction_data.as_bytes(), &tx.signature) {
-                    println!("Ошибка. Неверная подпись в транзакции {} -> {}: {}", tx.from, tx.to, tx.amount);
-                    return;
-                }
+    // число блоков, подтверждающих блок на этой высоте, включая его самого
+    pub fn confirmations(&self, height: u64) -> u64 {
+        (self.tip_height() + 1).saturating_sub(height)
+    }
 
-                let balance = self.load_balance(&tx.from);
-                let total_amount = tx.amount + TRANSACTION_FEE;
-                if total_amount > balance {
-                    println!(
-                        "Ошибка. Недостаточно средств для транзакции {} -> {}: {} с комиссией {}",
-                        tx.from, tx.to, tx.amount, TRANSACTION_FEE
-                    );
-                    return;
-                }
-            }
-        }
+    pub fn block_by_hash(&self, hash: &str) -> Option<Block> {
+        let height = self.db.get(format!("{}{}", BLOCK_HASH_PREFIX, hash)).unwrap()?;
+        self.load_block(bincode::deserialize(&height).unwrap())
+    }
 
-        let total_fees = transactions
-            .iter()
-            .map(|_| TRANSACTION_FEE)
-            .sum::<f64>();
+    // блоки от вершины к первому блоку
+    pub fn blocks(&self, offset: usize, limit: usize) -> Vec<Block> {
+        let Some(start) = self.tip_height().checked_sub(offset as u64) else {
+            return vec![];
+        };
+        (0..=start)
+            .rev()
+            .take(limit)
+            .map(|height| self.load_block(height).unwrap())
+            .collect()
+    }
 
-        let mut block_transactions = vec![];
+    pub fn find_transaction(&self, txid: &str) -> Option<(TxLocation, Transaction)> {
+        let location = self.db.get(format!("{}{}", TXID_PREFIX, txid)).unwrap()?;
+        let location: TxLocation = bincode::deserialize(&location).unwrap();
+        let block = self.load_block(location.height).unwrap();
+        let tx = block.transactions[location.position as usize].clone();
+        Some((location, tx))
+    }
 
-        block_transactions.push(Transaction {
-            from: NETWORK_ADDRESS.to_string(),
-            to: miner_address.to_string(),
-            amount: BLOCK_REWARD + total_fees,
-            signature: vec![],
-            public_key: vec![],
-        });
+    pub fn add_block(&mut self, miner_address: &str, transactions: Vec<Transaction>) -> Result<Block, BlockError> {
+        let last_block = self.latest_block().unwrap();
+        let span = info_span!("block", height = last_block.index + 1, hash = field::Empty);
+        let _span = span.enter();
 
-        block_transactions.extend(transactions.clone());
+        let mut block_transactions = vec![Transaction::coinbase(
+            &self.spec.network_address,
+            miner_address,
+            self.block_reward(last_block.index + 1, &transactions),
+            last_block.index + 1,
+        )];
+        block_transactions.extend(transactions);
+
+        self.validate_transactions(last_block.index + 1, &block_transactions)?;
 
-        let last_block = self.latest_block().unwrap();
         let new_block = Block::new(
             last_block.index + 1,
-            now(),
-            block_transactions.clone(),
+            now().max(last_block.timestamp + 1),
+            block_transactions,
             last_block.hash.clone(),
-            self.difficulty,
+            self.spec.difficulty,
         );
+        span.record("hash", field::display(&new_block.hash));
 
-        let serialized_block = bincode::serialize(&new_block).unwrap();
-        let block_key = format!("{}{}", BLOCK_PREFIX, new_block.index);
-        self.db.insert(block_key, serialized_block).unwrap();
-        self.db.flush().unwrap();
-
-        for tx in transactions {
-            if tx.from != NETWORK_ADDRESS {
-                let balance = self.load_balance(&tx.from);
-                self.save_balance(&tx.from, balance - tx.amount);
-            }
-            self.save_balance(&tx.to, self.load_balance(&tx.to) + tx.amount);
-        }
+        self.save_block(&new_block);
+        info!(transactions = new_block.transactions.len(), "блок добыт");
+        Ok(new_block)
     }
 
-    pub fn add_block_from_p2p(&mut self, block: Block) -> bool {
+    pub fn add_block_from_p2p(&mut self, block: Block) -> Result<(), BlockError> {
         let latest_block = self.latest_block().unwrap();
 
         if block.index != latest_block.index + 1 {
-            return false;
+            return Err(BlockError::InvalidIndex);
         }
 
         if block.prev_hash != latest_block.hash {
-            return false;
+            return Err(BlockError::InvalidPrevHash);
         }
 
         if block.hash != block.calculate_hash() {
-            return false;
+            return Err(BlockError::InvalidHash);
+        }
+
+        if !block.hash.starts_with(&"0".repeat(self.spec.difficulty)) {
+            return Err(BlockError::InsufficientWork);
+        }
+
+        if block.timestamp <= latest_block.timestamp || block.timestamp > now() + MAX_FUTURE_BLOCK_TIME {
+            return Err(BlockError::InvalidTimestamp);
+        }
+
+        self.validate_transactions(block.index, &block.transactions)?;
+
+        self.save_block(&block);
+        Ok(())
+    }
+
+    // первой в блоке всегда идет coinbase-транзакция с наградой майнеру,
+    // остальные транзакции проверяются по подписи и по средствам отправителя
+    // с учетом уже потраченного в этом же блоке
+    fn validate_transactions(&self, height: u64, transactions: &[Transaction]) -> Result<(), BlockError> {
+        let Some((coinbase, transactions)) = transactions.split_first() else {
+            return Err(BlockError::MissingCoinbase);
+        };
+
+        if !coinbase.is_coinbase(&self.spec.network_address) {
+            return Err(BlockError::MissingCoinbase);
+        }
+
+        let expected_coinbase = Transaction::coinbase(
+            &self.spec.network_address,
+            &coinbase.to,
+            self.block_reward(height, transactions),
+            height,
+        );
+        // id покрывает все поля, поэтому у награды нет свободных полей и второго id
+        if coinbase.id() != expected_coinbase.id() {
+            return Err(BlockError::InvalidReward);
+        }
+
+        if !address::is_valid(&coinbase.to, &self.spec.address_prefix) {
+            return Err(BlockError::InvalidAddress);
+        }
+
+        let immature_rewards = self.immature_rewards(height);
+        let mut spent_balances: HashMap<&str, f64> = HashMap::new();
+        let mut next_nonces: HashMap<&str, u64> = HashMap::new();
+        let mut spent_outputs: HashSet<&OutPoint> = HashSet::new();
+        let mut signed_data = HashSet::new();
+        let mut schnorr_batch: Vec<(&[u8], String, &[u8])> = vec![];
+
+        for tx in transactions {
+            if tx.is_coinbase(&self.spec.network_address) {
+                return Err(BlockError::UnexpectedCoinbase);
+            }
+
+            if !tx.amount.is_finite() || tx.amount <= 0.0 {
+                return Err(BlockError::InvalidAmount);
+            }
+
+            if !tx.fee.is_finite() || tx.fee < self.spec.transaction_fee {
+                return Err(BlockError::InsufficientFee);
+            }
+
+            if !address::is_valid(&tx.to, &self.spec.address_prefix) {
+                return Err(BlockError::InvalidAddress);
+            }
+
+            let data = tx.signing_data();
+            let signed_keys: Vec<(&[u8], &[u8])> = match &tx.multisig {
+                Some(multisig) => {
+                    if !tx.signature.is_empty()
+                        || !tx.public_key.is_empty()
+                        || !multisig.is_well_formed()
+                        || multisig.address(&self.spec.address_prefix) != tx.from
+                        || multisig.signature_count() < multisig.threshold as usize
+                    {
+                        return Err(BlockError::InvalidSignature);
+                    }
+                    multisig.signed_keys().collect()
+                }
+                None => {
+                    if address::from_public_key(&tx.public_key, &self.spec.address_prefix) != tx.from {
+                        return Err(BlockError::InvalidSignature);
+                    }
+                    vec![(tx.public_key.as_slice(), tx.signature.as_slice())]
+                }
+            };
+            match tx.signature_type {
+                SignatureScheme::Ecdsa => {
+                    for (public_key, signature) in signed_keys {
+                        if !Wallet::verify(public_key, data.as_bytes(), signature, SignatureScheme::Ecdsa) {
+                            warn!(from = %tx.from, to = %tx.to, amount = tx.amount, "неверная подпись транзакции");
+                            return Err(BlockError::InvalidSignature);
+                        }
+                    }
+                }
+                // подписи Шнорра проверяются одним пакетом после остальных проверок блока
+                SignatureScheme::Schnorr => {
+                    for (public_key, signature) in signed_keys {
+                        schnorr_batch.push((public_key, data.clone(), signature));
+                    }
+                }
+            }
+
+            // подписи Шнорра случайны, а ECDSA детерминирована, поэтому повтор ищется по подписываемым данным
+            if !signed_data.insert(data) {
+                return Err(BlockError::DuplicateTransaction);
+            }
+
+            match self.spec.ledger {
+                Ledger::Account => {
+                    self.validate_account_spend(tx, &immature_rewards, &mut spent_balances, &mut next_nonces)?
+                }
+                Ledger::Utxo => self.validate_utxo_spend(height, tx, &mut spent_outputs)?,
+            }
+        }
+
+        let batch = schnorr_batch
+            .iter()
+            .map(|(public_key, data, signature)| (*public_key, data.as_bytes(), *signature))
+            .collect::<Vec<_>>();
+        if !Wallet::verify_schnorr_batch(&batch) {
+            warn!(height, "неверная подпись Шнорра в блоке");
+            return Err(BlockError::InvalidSignature);
+        }
+
+        Ok(())
+    }
+
+    fn validate_account_spend<'a>(
+        &self,
+        tx: &'a Transaction,
+        immature_rewards: &HashMap<String, f64>,
+        spent_balances: &mut HashMap<&'a str, f64>,
+        next_nonces: &mut HashMap<&'a str, u64>,
+    ) -> Result<(), BlockError> {
+        if !tx.inputs.is_empty() || !tx.outputs.is_empty() {
+            return Err(BlockError::LedgerMismatch);
+        }
+
+        let next_nonce = next_nonces.entry(tx.from.as_str()).or_insert_with(|| self.next_nonce(&tx.from));
+        if tx.nonce != *next_nonce {
+            return Err(BlockError::InvalidNonce);
+        }
+        *next_nonce += 1;
+
+        let balance = self.stored_balance(&tx.from);
+        let immature = immature_rewards.get(&tx.from).copied().unwrap_or(0.0);
+        let total_spent = spent_balances.entry(tx.from.as_str()).or_insert(0.0);
+        *total_spent += tx.amount + tx.fee;
+        if *total_spent > balance {
+            warn!(from = %tx.from, to = %tx.to, amount = tx.amount, fee = tx.fee, "недостаточно средств для транзакции");
+            return Err(BlockError::InsufficientFunds);
+        }
+        if *total_spent > balance - immature {
+            return Err(BlockError::ImmatureCoinbase);
+        }
+        Ok(())
+    }
+
+    // первый выход транзакции - перевод получателю на сумму amount,
+    // остальные выходы - как правило, сдача отправителю
+    fn validate_utxo_spend<'a>(
+        &self,
+        height: u64,
+        tx: &'a Transaction,
+        spent_outputs: &mut HashSet<&'a OutPoint>,
+    ) -> Result<(), BlockError> {
+        if tx.inputs.is_empty() || tx.outputs.first() != Some(&TxOutput { address: tx.to.clone(), amount: tx.amount }) {
+            return Err(BlockError::LedgerMismatch);
+        }
+
+        if tx.outputs.iter().any(|output| !output.amount.is_finite() || output.amount <= 0.0) {
+            return Err(BlockError::InvalidAmount);
+        }
+
+        if tx.outputs.iter().any(|output| !address::is_valid(&output.address, &self.spec.address_prefix)) {
+            return Err(BlockError::InvalidAddress);
+        }
+
+        let mut inputs_total = 0.0;
+        for input in &tx.inputs {
+            if !spent_outputs.insert(input) {
+                return Err(BlockError::DoubleSpend);
+            }
+            match self.load_utxo(input) {
+                Some(entry) if entry.output.address == tx.from => {
+                    if !self.is_spendable(&entry, height) {
+                        return Err(BlockError::ImmatureCoinbase);
+                    }
+                    inputs_total += entry.output.amount;
+                }
+                _ => return Err(BlockError::UnknownInput),
+            }
+        }
+
+        let outputs_total = tx.outputs.iter().map(|output| output.amount).sum::<f64>();
+        if inputs_total < outputs_total + tx.fee - AMOUNT_EPSILON {
+            return Err(BlockError::InsufficientFunds);
+        }
+        if inputs_total > outputs_total + tx.fee + AMOUNT_EPSILON {
+            return Err(BlockError::InvalidFee);
+        }
+        Ok(())
+    }
+
+    // готовит неподписанную транзакцию: в режиме учета по счетам ставит следующий номер
+    // отправителя, в режиме UTXO подбирает его выходы и возвращает ему сдачу
+    pub fn prepare_transaction(&self, from: &str, to: &str, amount: f64) -> Result<Transaction, BlockError> {
+        let mut tx = Transaction {
+            from: from.to_string(),
+            to: to.to_string(),
+            amount,
+            fee: self.spec.transaction_fee,
+            nonce: 0,
+            inputs: vec![],
+            outputs: vec![],
+            signature: vec![],
+            public_key: vec![],
+            signature_type: SignatureScheme::Ecdsa,
+            multisig: None,
+        };
+
+        if self.spec.ledger == Ledger::Account {
+            tx.nonce = self.next_nonce(from);
+        }
+
+        if self.spec.ledger == Ledger::Utxo {
+            let height = self.latest_block().unwrap().index + 1;
+            let required = amount + tx.fee;
+            let mut collected = 0.0;
+            for (outpoint, entry) in self.load_utxos(from) {
+                if collected >= required {
+                    break;
+                }
+                if !self.is_spendable(&entry, height) {
+                    continue;
+                }
+                collected += entry.output.amount;
+                tx.inputs.push(outpoint);
+            }
+            if collected < required {
+                return Err(BlockError::InsufficientFunds);
+            }
+
+            tx.outputs.push(TxOutput { address: to.to_string(), amount });
+            let change = collected - required;
+            if change > AMOUNT_EPSILON {
+                tx.outputs.push(TxOutput { address: from.to_string(), amount: change });
+            }
+        }
+
+        Ok(tx)
+    }
+
+    fn block_reward(&self, height: u64, transactions: &[Transaction]) -> f64 {
+        let total_fees = transactions
+            .iter()
+            .map(|tx| tx.fee)
+            .sum::<f64>();
+
+        self.spec.subsidy_at(height) + total_fees
+    }
+
+    // все выпущенные монеты: награды майнерам и начальное распределение,
+    // комиссии только перекладываются от отправителей к майнерам
+    pub fn circulating_supply(&self) -> f64 {
+        let mut supply = 0.0;
+        for block in self.load_blockchain() {
+            for tx in &block.transactions {
+                if tx.is_coinbase(&self.spec.network_address) {
+                    supply += tx.amount;
+                } else {
+                    supply -= tx.fee;
+                }
+            }
         }
+        supply
+    }
+
+    // coinbase-выход созревает через coinbase_maturity блоков,
+    // начальное распределение из первого блока можно тратить сразу
+    fn is_spendable(&self, entry: &UtxoEntry, height: u64) -> bool {
+        !entry.coinbase || entry.height == 0 || height - entry.height >= self.spec.coinbase_maturity
+    }
 
-        if !block.hash.starts_with(&"0".repeat(self.difficulty)) {
-            return false;
+    // еще не созревшие награды майнерам для блока на высоте height (режим балансов)
+    fn immature_rewards(&self, height: u64) -> HashMap<String, f64> {
+        let mut rewards = HashMap::new();
+        let first = (height + 1).saturating_sub(self.spec.coinbase_maturity).max(1);
+        for index in first..height {
+            if let Some(block) = self.load_block(index) {
+                let coinbase = &block.transactions[0];
+                *rewards.entry(coinbase.to.clone()).or_insert(0.0) += coinbase.amount;
+            }
         }
+        rewards
+    }
 
-        let encoded_block = bincode::serialize(&block).unwrap();
+    fn save_block(&self, block: &Block) {
+        let encoded_block = bincode::serialize(block).unwrap();
         let block_key = format!("{}{}", BLOCK_PREFIX, block.index);
         self.db.insert(block_key, encoded_block).unwrap();
-        self.db.flush().unwrap();
 
-        for tx in block.transactions {
-            if tx.from != NETWORK_ADDRESS {
-                let balance = self.load_balance(&tx.from);
-                self.save_balance(&tx.from, balance - tx.amount);
+        for tx in &block.transactions {
+            match self.spec.ledger {
+                Ledger::Account => {
+                    if !tx.is_coinbase(&self.spec.network_address) {
+                        let balance = self.stored_balance(&tx.from);
+                        self.save_balance(&tx.from, balance - tx.amount - tx.fee);
+                        self.save_nonce(&tx.from, tx.nonce + 1);
+                    }
+
+                    let to_balance = self.stored_balance(&tx.to);
+                    self.save_balance(&tx.to, to_balance + tx.amount);
+                }
+                Ledger::Utxo => {
+                    for input in &tx.inputs {
+                        self.db.remove(Self::utxo_key(input)).unwrap();
+                    }
+
+                    let txid = tx.id();
+                    for (vout, output) in tx.outputs.iter().enumerate() {
+                        let outpoint = OutPoint { txid: txid.clone(), vout: vout as u32 };
+                        let entry = UtxoEntry {
+                            output: output.clone(),
+                            height: block.index,
+                            coinbase: tx.is_coinbase(&self.spec.network_address),
+                        };
+                        self.db.insert(Self::utxo_key(&outpoint), bincode::serialize(&entry).unwrap()).unwrap();
+                    }
+                }
             }
+        }
+        self.index_block(block);
+        self.db.flush().unwrap();
+        self.publish_block_events(block);
+    }
+
+    fn publish_block_events(&self, block: &Block) {
+        self.events.publish(Event::Tip {
+            height: block.index,
+            hash: block.hash.clone(),
+            txids: block.transactions.iter().map(|tx| tx.id()).collect(),
+        });
 
-            let to_balance = self.load_balance(&tx.to);
-            self.save_balance(&tx.to, to_balance + tx.amount);
+        let mut changed = HashSet::new();
+        for tx in &block.transactions {
+            let addresses = self.tx_addresses(tx);
+            changed.extend(addresses.iter().map(|address| address.to_string()));
+            self.events.publish(Event::Transaction {
+                txid: tx.id(),
+                height: block.index,
+                addresses: addresses.into_iter().cloned().collect(),
+            });
+        }
+
+        // с новым блоком созревает награда майнера из блока на coinbase_maturity блоков раньше
+        let matured = (block.index + 1).checked_sub(self.spec.coinbase_maturity).filter(|&height| height > 0);
+        if let Some(matured_block) = matured.and_then(|height| self.load_block(height)) {
+            changed.insert(matured_block.transactions[0].to.clone());
+        }
+
+        for address in changed {
+            let balance = self.load_balance(&address);
+            self.events.publish(Event::Balance { address, height: block.index, balance });
+        }
+    }
+
+    // адреса, средства которых затрагивает транзакция
+    fn tx_addresses<'a>(&self, tx: &'a Transaction) -> HashSet<&'a String> {
+        let mut addresses = HashSet::new();
+        if !tx.is_coinbase(&self.spec.network_address) {
+            addresses.insert(&tx.from);
+        }
+        addresses.insert(&tx.to);
+        addresses.extend(tx.outputs.iter().map(|output| &output.address));
+        addresses
+    }
+
+    // ключ addrtx:{адрес}:{высота}:{номер} упорядочивает историю адреса по времени,
+    // blockhash: и txid: ведут к блоку и транзакции, meta:tip хранит высоту вершины
+    fn index_block(&self, block: &Block) {
+        let height = bincode::serialize(&block.index).unwrap();
+        self.db.insert(format!("{}{}", BLOCK_HASH_PREFIX, block.hash), height.clone()).unwrap();
+        if block.index >= self.tip_height() {
+            self.db.insert(TIP_KEY, height).unwrap();
+        }
+
+        for (position, tx) in block.transactions.iter().enumerate() {
+            let entry = TxLocation {
+                txid: tx.id(),
+                height: block.index,
+                block_hash: block.hash.clone(),
+                position: position as u32,
+            };
+            let encoded_entry = bincode::serialize(&entry).unwrap();
+            self.db.insert(format!("{}{}", TXID_PREFIX, entry.txid), encoded_entry.clone()).unwrap();
+            for address in self.tx_addresses(tx) {
+                let key = format!("{}{}:{:020}:{:010}", ADDRESS_TX_PREFIX, address, block.index, position);
+                self.db.insert(key, encoded_entry.clone()).unwrap();
+            }
         }
-        true
     }
 
     pub fn is_valid(&self) -> bool {
@@ -173,7 +690,7 @@ impl Blockchain {
             if current.prev_hash != previous.hash {
                 return false;
             }
-            if !current.hash.starts_with(&"0".repeat(self.difficulty)) {
+            if !current.hash.starts_with(&"0".repeat(self.spec.difficulty)) {
                 return false;
             }
         }
@@ -182,7 +699,34 @@ impl Blockchain {
 }
 
 impl Blockchain {
-    pub fn load_balance(&self, address: &str) -> f64 {
+    // баланс с точки зрения следующего блока цепочки
+    pub fn load_balance(&self, address: &str) -> Balance {
+        let height = self.latest_block().unwrap().index + 1;
+
+        if self.spec.ledger == Ledger::Utxo {
+            let mut balance = Balance::default();
+            for (_, entry) in self.load_utxos(address) {
+                self.add_utxo_to_balance(&mut balance, &entry, height);
+            }
+            return balance;
+        }
+
+        let immature = self.immature_rewards(height).get(address).copied().unwrap_or(0.0);
+        Balance {
+            mature: self.stored_balance(address) - immature,
+            immature,
+        }
+    }
+
+    fn add_utxo_to_balance(&self, balance: &mut Balance, entry: &UtxoEntry, height: u64) {
+        if self.is_spendable(entry, height) {
+            balance.mature += entry.output.amount;
+        } else {
+            balance.immature += entry.output.amount;
+        }
+    }
+
+    fn stored_balance(&self, address: &str) -> f64 {
         let key = format!("{}{}", BALANCE_PREFIX, address);
         if let Ok(Some(b)) = self.db.get(key) {
             let balance: f64 = bincode::deserialize(&b).unwrap();
@@ -192,13 +736,40 @@ impl Blockchain {
         }
     }
 
+    // номер, который должна нести следующая транзакция адреса в режиме учета по счетам
+    pub fn next_nonce(&self, address: &str) -> u64 {
+        let key = format!("{}{}", NONCE_PREFIX, address);
+        self.db
+            .get(key)
+            .unwrap()
+            .map(|val| bincode::deserialize(&val).unwrap())
+            .unwrap_or(0)
+    }
+
+    fn save_nonce(&self, address: &str, nonce: u64) {
+        let key = format!("{}{}", NONCE_PREFIX, address);
+        self.db.insert(key, bincode::serialize(&nonce).unwrap()).unwrap();
+    }
+
     fn save_balance(&self, address: &str, balance: f64) {
         let key = format!("{}{}", BALANCE_PREFIX, address);
         let bytes_balance = bincode::serialize(&balance).unwrap();
         self.db.insert(key, bytes_balance).unwrap();
     }
 
-    pub fn load_balances(&self) -> Vec<(String, f64)> {
+    pub fn load_balances(&self) -> Vec<(String, Balance)> {
+        let height = self.latest_block().unwrap().index + 1;
+
+        if self.spec.ledger == Ledger::Utxo {
+            let mut balances: HashMap<String, Balance> = HashMap::new();
+            for (_, entry) in self.scan_utxos() {
+                let balance = balances.entry(entry.output.address.clone()).or_default();
+                self.add_utxo_to_balance(balance, &entry, height);
+            }
+            return balances.into_iter().collect();
+        }
+
+        let immature_rewards = self.immature_rewards(height);
         let mut balances = vec![];
         for key in self.db.scan_prefix(BALANCE_PREFIX) {
             let (key, val) = key.unwrap();
@@ -206,8 +777,440 @@ impl Blockchain {
             let key_str = String::from_utf8(key.to_vec()).unwrap();
             let address = key_str.strip_prefix(BALANCE_PREFIX).unwrap().to_string();
             let balance: f64 = bincode::deserialize(&val).unwrap();
-            balances.push((address, balance));
+            let immature = immature_rewards.get(&address).copied().unwrap_or(0.0);
+            balances.push((address, Balance { mature: balance - immature, immature }));
         }
         balances
     }
+
+    fn utxo_key(outpoint: &OutPoint) -> String {
+        format!("{}{}:{}", UTXO_PREFIX, outpoint.txid, outpoint.vout)
+    }
+
+    pub fn load_utxo(&self, outpoint: &OutPoint) -> Option<UtxoEntry> {
+        self.db
+            .get(Self::utxo_key(outpoint))
+            .unwrap()
+            .map(|val| bincode::deserialize(&val).unwrap())
+    }
+
+    fn scan_utxos(&self) -> Vec<(OutPoint, UtxoEntry)> {
+        let mut utxos = vec![];
+        for entry in self.db.scan_prefix(UTXO_PREFIX) {
+            let (key, val) = entry.unwrap();
+
+            let key_str = String::from_utf8(key.to_vec()).unwrap();
+            let (txid, vout) = key_str.strip_prefix(UTXO_PREFIX).unwrap().split_once(':').unwrap();
+            let outpoint = OutPoint { txid: txid.to_string(), vout: vout.parse().unwrap() };
+            utxos.push((outpoint, bincode::deserialize(&val).unwrap()));
+        }
+        utxos
+    }
+
+    pub fn load_utxos(&self, address: &str) -> Vec<(OutPoint, UtxoEntry)> {
+        self.scan_utxos()
+            .into_iter()
+            .filter(|(_, entry)| entry.output.address == address)
+            .collect()
+    }
+
+    // история адреса от новых транзакций к старым и общее число записей
+    pub fn address_history(&self, address: &str, offset: usize, limit: usize) -> (usize, Vec<(TxLocation, Transaction)>) {
+        let prefix = format!("{}{}:", ADDRESS_TX_PREFIX, address);
+        let total = self.db.scan_prefix(&prefix).count();
+
+        let history = self
+            .db
+            .scan_prefix(&prefix)
+            .rev()
+            .skip(offset)
+            .take(limit)
+            .map(|entry| {
+                let (_, val) = entry.unwrap();
+                let entry: TxLocation = bincode::deserialize(&val).unwrap();
+                let block = self.load_block(entry.height).unwrap();
+                let tx = block.transactions[entry.position as usize].clone();
+                (entry, tx)
+            })
+            .collect();
+        (total, history)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::chain_spec::Allocation;
+    use crate::transaction::Multisig;
+
+    const PREFIX: &str = "rb";
+
+    // кошелек с закрытым ключом из одного повторенного байта
+    fn wallet(seed: u8) -> Wallet {
+        Wallet::import_private_key(&hex::encode([seed; 32])).unwrap()
+    }
+
+    fn address(seed: u8) -> String {
+        wallet(seed).address(PREFIX)
+    }
+
+    // без майнинга и со 100 монетами у кошелька 1
+    fn spec(ledger: Ledger) -> ChainSpec {
+        ChainSpec {
+            allocations: vec![Allocation { address: address(1), amount: 100.0 }],
+            coinbase_maturity: 2,
+            difficulty: 0,
+            ledger,
+            ..ChainSpec::default()
+        }
+    }
+
+    // у каждого теста своя база во временном каталоге
+    fn blockchain(name: &str, spec: ChainSpec) -> Blockchain {
+        let path = std::env::temp_dir().join(format!("blockchain_{}", name));
+        let _ = std::fs::remove_dir_all(&path);
+        Blockchain::new(path.to_str().unwrap(), spec)
+    }
+
+    fn sign(tx: &mut Transaction, wallet: &Wallet) {
+        tx.public_key = wallet.public_key.to_sec1_bytes().to_vec();
+        tx.signature = wallet.sign(tx.signing_data().as_bytes(), tx.signature_type);
+    }
+
+    fn transfer(blockchain: &Blockchain, from: u8, to: u8, amount: f64) -> Transaction {
+        let mut tx = blockchain.prepare_transaction(&address(from), &address(to), amount).unwrap();
+        sign(&mut tx, &wallet(from));
+        tx
+    }
+
+    // следующий блок в том виде, в каком его прислал бы другой узел
+    fn next_block(blockchain: &Blockchain, transactions: Vec<Transaction>) -> Block {
+        let last = blockchain.latest_block().unwrap();
+        let height = last.index + 1;
+        let reward = blockchain.block_reward(height, &transactions);
+        let mut block_transactions = vec![Transaction::coinbase(&blockchain.spec.network_address, &address(9), reward, height)];
+        block_transactions.extend(transactions);
+        Block::new(height, last.timestamp + 1, block_transactions, last.hash, blockchain.spec.difficulty)
+    }
+
+    // пересчитывает хеш после правки блока
+    fn rehash(block: Block) -> Block {
+        Block::with_value(block.index, block.timestamp, block.transactions, block.prev_hash, block.value)
+    }
+
+    fn assert_rejected(blockchain: &mut Blockchain, block: Block, expected: BlockError) {
+        let result = blockchain.add_block_from_p2p(block);
+        assert_eq!(format!("{:?}", result), format!("{:?}", Err::<(), _>(expected)));
+    }
+
+    #[test]
+    fn accepts_valid_peer_block() {
+        let mut blockchain = blockchain("valid_peer_block", spec(Ledger::Account));
+        let tx = transfer(&blockchain, 1, 2, 10.0);
+        blockchain.add_block_from_p2p(next_block(&blockchain, vec![tx])).unwrap();
+
+        assert_eq!(blockchain.tip_height(), 1);
+        assert_eq!(blockchain.load_balance(&address(2)).mature, 10.0);
+        assert!((blockchain.load_balance(&address(1)).mature - 89.99).abs() < 1e-9);
+    }
+
+    #[test]
+    fn rejects_peer_block_out_of_place() {
+        let mut blockchain = blockchain("peer_block_place", spec(Ledger::Account));
+        let block = next_block(&blockchain, vec![]);
+
+        assert_rejected(&mut blockchain, rehash(Block { index: 2, ..block.clone() }), BlockError::InvalidIndex);
+        assert_rejected(
+            &mut blockchain,
+            rehash(Block { prev_hash: "0".repeat(64), ..block.clone() }),
+            BlockError::InvalidPrevHash,
+        );
+        assert_rejected(&mut blockchain, Block { value: block.value + 1, ..block.clone() }, BlockError::InvalidHash);
+        let genesis_timestamp = blockchain.spec.genesis_timestamp;
+        assert_rejected(
+            &mut blockchain,
+            rehash(Block { timestamp: genesis_timestamp, ..block.clone() }),
+            BlockError::InvalidTimestamp,
+        );
+        assert_rejected(
+            &mut blockchain,
+            rehash(Block { timestamp: now() + 2 * MAX_FUTURE_BLOCK_TIME, ..block }),
+            BlockError::InvalidTimestamp,
+        );
+    }
+
+    #[test]
+    fn rejects_peer_block_without_work() {
+        let mut spec = ChainSpec { difficulty: 1, ..spec(Ledger::Account) };
+        spec.genesis_nonce = spec.mine_genesis_block().value;
+        let mut blockchain = blockchain("peer_block_work", spec);
+
+        let block = next_block(&blockchain, vec![]);
+        let unmined = (0..)
+            .map(|value| rehash(Block { value, ..block.clone() }))
+            .find(|candidate| !candidate.hash.starts_with('0'))
+            .unwrap();
+        assert_rejected(&mut blockchain, unmined, BlockError::InsufficientWork);
+    }
+
+    #[test]
+    fn rejects_invalid_coinbase() {
+        let mut blockchain = blockchain("coinbase", spec(Ledger::Account));
+        let tx = transfer(&blockchain, 1, 2, 10.0);
+        let block = next_block(&blockchain, vec![tx.clone()]);
+        let coinbase = block.transactions[0].clone();
+        let with_coinbase = |coinbase: Transaction| {
+            rehash(Block { transactions: vec![coinbase, tx.clone()], ..block.clone() })
+        };
+
+        assert_rejected(&mut blockchain, rehash(Block { transactions: vec![], ..block.clone() }), BlockError::MissingCoinbase);
+        assert_rejected(&mut blockchain, with_coinbase(tx.clone()), BlockError::MissingCoinbase);
+        assert_rejected(
+            &mut blockchain,
+            with_coinbase(Transaction { amount: coinbase.amount + 1.0, ..coinbase.clone() }),
+            BlockError::InvalidReward,
+        );
+        // лишние поля награды меняли бы ее id, не меняя суммы
+        assert_rejected(&mut blockchain, with_coinbase(Transaction { fee: 1.0, ..coinbase.clone() }), BlockError::InvalidReward);
+        assert_rejected(&mut blockchain, with_coinbase(Transaction { nonce: 1, ..coinbase.clone() }), BlockError::InvalidReward);
+        assert_rejected(
+            &mut blockchain,
+            with_coinbase(Transaction { public_key: vec![1], ..coinbase.clone() }),
+            BlockError::InvalidReward,
+        );
+        let to = "miner".to_string();
+        assert_rejected(
+            &mut blockchain,
+            with_coinbase(Transaction { outputs: vec![TxOutput { address: to.clone(), amount: coinbase.amount }], to, ..coinbase.clone() }),
+            BlockError::InvalidAddress,
+        );
+
+        let mut block = next_block(&blockchain, vec![]);
+        block.transactions.push(block.transactions[0].clone());
+        assert_rejected(&mut blockchain, rehash(block), BlockError::UnexpectedCoinbase);
+    }
+
+    #[test]
+    fn rejects_invalid_transactions() {
+        let mut blockchain = blockchain("transactions", spec(Ledger::Account));
+        let tx = transfer(&blockchain, 1, 2, 10.0);
+        let signed = |mut tx: Transaction| {
+            sign(&mut tx, &wallet(1));
+            tx
+        };
+
+        for (tx, expected) in [
+            (signed(Transaction { amount: 0.0, ..tx.clone() }), BlockError::InvalidAmount),
+            (signed(Transaction { amount: f64::NAN, ..tx.clone() }), BlockError::InvalidAmount),
+            (signed(Transaction { fee: 0.0, ..tx.clone() }), BlockError::InsufficientFee),
+            (signed(Transaction { to: "rb1".to_string(), ..tx.clone() }), BlockError::InvalidAddress),
+            (Transaction { amount: 20.0, ..tx.clone() }, BlockError::InvalidSignature),
+            (Transaction { from: address(3), ..tx.clone() }, BlockError::InvalidSignature),
+            (signed(Transaction { amount: 200.0, ..tx.clone() }), BlockError::InsufficientFunds),
+        ] {
+            let block = next_block(&blockchain, vec![tx]);
+            assert_rejected(&mut blockchain, block, expected);
+        }
+
+        let block = next_block(&blockchain, vec![tx.clone(), tx]);
+        assert_rejected(&mut blockchain, block, BlockError::DuplicateTransaction);
+        assert_eq!(blockchain.tip_height(), 0);
+    }
+
+    #[test]
+    fn utxo_ledger_rejects_double_spend() {
+        let mut blockchain = blockchain("utxo_double_spend", spec(Ledger::Utxo));
+        let tx = transfer(&blockchain, 1, 2, 10.0);
+        let other = transfer(&blockchain, 1, 3, 20.0);
+
+        let mut repeated_input = tx.clone();
+        repeated_input.inputs.push(repeated_input.inputs[0].clone());
+        sign(&mut repeated_input, &wallet(1));
+        let block = next_block(&blockchain, vec![repeated_input]);
+        assert_rejected(&mut blockchain, block, BlockError::DoubleSpend);
+
+        let block = next_block(&blockchain, vec![tx.clone(), other]);
+        assert_rejected(&mut blockchain, block, BlockError::DoubleSpend);
+
+        blockchain.add_block_from_p2p(next_block(&blockchain, vec![tx.clone()])).unwrap();
+        let mut spent_again = tx;
+        spent_again.amount = 5.0;
+        spent_again.outputs[0].amount = 5.0;
+        spent_again.outputs[1].amount += 5.0;
+        sign(&mut spent_again, &wallet(1));
+        let block = next_block(&blockchain, vec![spent_again]);
+        assert_rejected(&mut blockchain, block, BlockError::UnknownInput);
+    }
+
+    #[test]
+    fn utxo_ledger_checks_inputs_and_fee() {
+        let mut blockchain = blockchain("utxo_inputs", spec(Ledger::Utxo));
+        let tx = transfer(&blockchain, 1, 2, 10.0);
+        assert_eq!(tx.outputs[1], TxOutput { address: address(1), amount: 100.0 - 10.0 - tx.fee });
+        let signed = |mut tx: Transaction, from: u8| {
+            sign(&mut tx, &wallet(from));
+            tx
+        };
+
+        let mut unknown = tx.clone();
+        unknown.inputs[0].vout = 1;
+        let mut foreign = tx.clone();
+        foreign.from = address(3);
+        let mut overspent = tx.clone();
+        overspent.outputs[1].amount += 1.0;
+        let mut underpaid = tx.clone();
+        underpaid.outputs[1].amount -= 1.0;
+        let mut mismatched = tx.clone();
+        mismatched.outputs[0].amount = 9.0;
+
+        for (tx, expected) in [
+            (signed(unknown, 1), BlockError::UnknownInput),
+            (signed(foreign, 3), BlockError::UnknownInput),
+            (signed(overspent, 1), BlockError::InsufficientFunds),
+            // разница входов и выходов целиком уходит майнеру, поэтому должна совпадать с комиссией
+            (signed(underpaid, 1), BlockError::InvalidFee),
+            (signed(mismatched, 1), BlockError::LedgerMismatch),
+            (signed(Transaction { inputs: vec![], outputs: vec![], ..tx.clone() }, 1), BlockError::LedgerMismatch),
+        ] {
+            let block = next_block(&blockchain, vec![tx]);
+            assert_rejected(&mut blockchain, block, expected);
+        }
+
+        blockchain.add_block_from_p2p(next_block(&blockchain, vec![tx.clone()])).unwrap();
+        assert_eq!(blockchain.load_balance(&address(2)).mature, 10.0);
+        assert!((blockchain.load_balance(&address(1)).mature - 89.99).abs() < 1e-9);
+        assert_eq!(blockchain.load_balance(&address(9)).immature, blockchain.spec.subsidy_at(1) + tx.fee);
+    }
+
+    #[test]
+    fn account_ledger_rejects_utxo_transactions() {
+        let mut blockchain = blockchain("account_utxo_tx", spec(Ledger::Account));
+        let mut tx = transfer(&blockchain, 1, 2, 10.0);
+        tx.outputs.push(TxOutput { address: address(2), amount: 10.0 });
+        sign(&mut tx, &wallet(1));
+        let block = next_block(&blockchain, vec![tx]);
+        assert_rejected(&mut blockchain, block, BlockError::LedgerMismatch);
+    }
+
+    #[test]
+    fn account_ledger_rejects_immature_reward() {
+        let mut blockchain = blockchain("account_maturity", spec(Ledger::Account));
+        blockchain.add_block(&address(2), vec![]).unwrap();
+        let reward = blockchain.spec.subsidy_at(1);
+        assert_eq!(blockchain.load_balance(&address(2)).immature, reward);
+
+        let tx = transfer(&blockchain, 2, 3, 1.0);
+        let block = next_block(&blockchain, vec![tx.clone()]);
+        assert_rejected(&mut blockchain, block, BlockError::ImmatureCoinbase);
+
+        // награда из блока 1 созревает для блока 3 при coinbase_maturity = 2
+        blockchain.add_block(&address(9), vec![]).unwrap();
+        assert_eq!(blockchain.load_balance(&address(2)).mature, reward);
+        blockchain.add_block_from_p2p(next_block(&blockchain, vec![tx])).unwrap();
+    }
+
+    #[test]
+    fn utxo_ledger_rejects_immature_reward() {
+        let mut blockchain = blockchain("utxo_maturity", spec(Ledger::Utxo));
+        let coinbase = blockchain.add_block(&address(2), vec![]).unwrap().transactions[0].clone();
+        assert!(matches!(
+            blockchain.prepare_transaction(&address(2), &address(3), 1.0),
+            Err(BlockError::InsufficientFunds)
+        ));
+
+        let fee = blockchain.spec.transaction_fee;
+        let mut tx = Transaction {
+            from: address(2),
+            to: address(3),
+            amount: 1.0,
+            fee,
+            nonce: 0,
+            inputs: vec![OutPoint { txid: coinbase.id(), vout: 0 }],
+            outputs: vec![
+                TxOutput { address: address(3), amount: 1.0 },
+                TxOutput { address: address(2), amount: coinbase.amount - 1.0 - fee },
+            ],
+            signature: vec![],
+            public_key: vec![],
+            signature_type: SignatureScheme::Ecdsa,
+            multisig: None,
+        };
+        sign(&mut tx, &wallet(2));
+        let block = next_block(&blockchain, vec![tx.clone()]);
+        assert_rejected(&mut blockchain, block, BlockError::ImmatureCoinbase);
+
+        blockchain.add_block(&address(9), vec![]).unwrap();
+        blockchain.add_block_from_p2p(next_block(&blockchain, vec![tx])).unwrap();
+        assert_eq!(blockchain.load_balance(&address(3)).mature, 1.0);
+    }
+
+    // подписи участников seeds для транзакции с мультиподписного адреса
+    fn cosigned(tx: &Transaction, multisig: &Multisig, seeds: &[u8], scheme: SignatureScheme) -> Transaction {
+        let mut tx = Transaction { signature_type: scheme, multisig: Some(multisig.clone()), ..tx.clone() };
+        let data = tx.signing_data();
+        for seed in seeds {
+            assert!(tx.multisig.as_mut().unwrap().sign(&wallet(*seed), data.as_bytes(), scheme));
+        }
+        tx
+    }
+
+    #[test]
+    fn multisig_spend_needs_threshold_signatures() {
+        let mut keys: Vec<Vec<u8>> = (3..=5).map(|seed| wallet(seed).public_key.to_sec1_bytes().to_vec()).collect();
+        keys.sort();
+        let multisig = Multisig::new(2, keys);
+        let from = multisig.address(PREFIX);
+        let spec = ChainSpec { allocations: vec![Allocation { address: from.clone(), amount: 50.0 }], ..spec(Ledger::Account) };
+        let mut blockchain = blockchain("multisig", spec);
+        let tx = blockchain.prepare_transaction(&from, &address(2), 1.0).unwrap();
+
+        let mut lowered_threshold = cosigned(&tx, &multisig, &[3], SignatureScheme::Ecdsa);
+        lowered_threshold.multisig.as_mut().unwrap().threshold = 1;
+        let mut forged = cosigned(&tx, &multisig, &[3], SignatureScheme::Schnorr);
+        let outsider = wallet(6).sign(forged.signing_data().as_bytes(), SignatureScheme::Schnorr);
+        let signatures = &mut forged.multisig.as_mut().unwrap().signatures;
+        *signatures.iter_mut().find(|signature| signature.is_none()).unwrap() = Some(outsider);
+        let mut with_single_signature = cosigned(&tx, &multisig, &[3, 4], SignatureScheme::Ecdsa);
+        sign(&mut with_single_signature, &wallet(3));
+
+        for tx in [
+            cosigned(&tx, &multisig, &[3], SignatureScheme::Ecdsa),
+            cosigned(&tx, &multisig, &[5], SignatureScheme::Schnorr),
+            lowered_threshold,
+            forged,
+            with_single_signature,
+        ] {
+            let block = next_block(&blockchain, vec![tx]);
+            assert_rejected(&mut blockchain, block, BlockError::InvalidSignature);
+        }
+
+        let block = next_block(&blockchain, vec![cosigned(&tx, &multisig, &[3, 5], SignatureScheme::Ecdsa)]);
+        blockchain.add_block_from_p2p(block).unwrap();
+        let tx = blockchain.prepare_transaction(&from, &address(2), 1.0).unwrap();
+        let block = next_block(&blockchain, vec![cosigned(&tx, &multisig, &[4, 5], SignatureScheme::Schnorr)]);
+        blockchain.add_block_from_p2p(block).unwrap();
+        assert_eq!(blockchain.load_balance(&address(2)).mature, 2.0);
+    }
+
+    #[test]
+    fn account_ledger_rejects_replayed_nonce() {
+        let mut blockchain = blockchain("nonce", spec(Ledger::Account));
+        let first = transfer(&blockchain, 1, 2, 10.0);
+        let mut second = Transaction { nonce: first.nonce + 1, ..first.clone() };
+        sign(&mut second, &wallet(1));
+        let mut skipped = Transaction { nonce: first.nonce + 2, ..first.clone() };
+        sign(&mut skipped, &wallet(1));
+
+        let block = next_block(&blockchain, vec![skipped]);
+        assert_rejected(&mut blockchain, block, BlockError::InvalidNonce);
+        let block = next_block(&blockchain, vec![second.clone(), first.clone()]);
+        assert_rejected(&mut blockchain, block, BlockError::InvalidNonce);
+
+        blockchain.add_block_from_p2p(next_block(&blockchain, vec![first.clone(), second])).unwrap();
+        assert_eq!(blockchain.next_nonce(&address(1)), 2);
+
+        // подписанная транзакция из прошлого блока не проходит второй раз
+        let block = next_block(&blockchain, vec![first]);
+        assert_rejected(&mut blockchain, block, BlockError::InvalidNonce);
+    }
 }
@@ -0,0 +1,199 @@
+use crate::block::Block;
+use crate::i18n::{fail, Message};
+use crate::blockchain::Ledger;
+use crate::transaction::Transaction;
+use crate::utils::calculate_hash;
+use serde::{Deserialize, Serialize};
+use std::fs::read_to_string;
+
+// параметры сети, которые должны совпадать у всех ее узлов
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct ChainSpec {
+    pub network_id: String,
+    // с него начинаются все адреса сети
+    pub address_prefix: String,
+    pub genesis_timestamp: u128,
+    // заранее подобранное значение для первого блока, см. команду genesis
+    pub genesis_nonce: u64,
+    pub allocations: Vec<Allocation>,
+    // отправитель coinbase-транзакций
+    pub network_address: String,
+    // начальная награда за блок, уменьшается вдвое каждые halving_interval блоков
+    pub block_reward: f64,
+    pub halving_interval: u64,
+    // предел общего выпуска вместе с начальным распределением
+    pub max_supply: f64,
+    // минимальная комиссия транзакции
+    pub transaction_fee: f64,
+    // через сколько блоков награду майнеру можно тратить
+    pub coinbase_maturity: u64,
+    pub difficulty: usize,
+    pub ledger: Ledger,
+}
+
+// начальное распределение монет в первом блоке
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct Allocation {
+    pub address: String,
+    pub amount: f64,
+}
+
+impl ChainSpec {
+    pub fn load(path: &str) -> Self {
+        let content = read_to_string(path).unwrap_or_else(|e| fail(Message::OpenFailed(path.to_string(), e.to_string())));
+        serde_json::from_str(&content).unwrap_or_else(|e| fail(Message::InvalidChainSpec(e.to_string())))
+    }
+
+    pub fn load_or_default(path: Option<String>) -> Self {
+        path.as_deref().map(ChainSpec::load).unwrap_or_default()
+    }
+
+    // первый блок полностью определяется спецификацией, поэтому у всех узлов сети он одинаковый.
+    // вместо хеша предыдущего блока в нем записан хеш спецификации: узлы с разными правилами
+    // получают разные первые блоки и не соединяются друг с другом
+    pub fn genesis_block(&self) -> Block {
+        Block::with_value(
+            0,
+            self.genesis_timestamp,
+            self.genesis_transactions(),
+            self.consensus_hash(),
+            self.genesis_nonce,
+        )
+    }
+
+    pub fn mine_genesis_block(&self) -> Block {
+        Block::new(
+            0,
+            self.genesis_timestamp,
+            self.genesis_transactions(),
+            self.consensus_hash(),
+            self.difficulty,
+        )
+    }
+
+    // хеш всей спецификации, кроме genesis_nonce, который подбирается уже после нее
+    fn consensus_hash(&self) -> String {
+        let spec = ChainSpec { genesis_nonce: 0, ..self.clone() };
+        calculate_hash(serde_json::to_string(&spec).unwrap())
+    }
+
+    // новые монеты, которые получает майнер блока на высоте height (без комиссий)
+    pub fn subsidy_at(&self, height: u64) -> f64 {
+        if height == 0 {
+            return 0.0;
+        }
+        let remaining = (self.max_supply - self.scheduled_supply_before(height)).max(0.0);
+        self.scheduled_subsidy(height).min(remaining)
+    }
+
+    fn scheduled_subsidy(&self, height: u64) -> f64 {
+        let halvings = height.checked_div(self.halving_interval).unwrap_or(0);
+        if halvings >= 64 {
+            return 0.0;
+        }
+        self.block_reward / 2f64.powi(halvings as i32)
+    }
+
+    // выпуск по расписанию до блока height без учета предела,
+    // считается по эпохам между уменьшениями награды
+    fn scheduled_supply_before(&self, height: u64) -> f64 {
+        let mut supply = self.allocations.iter().map(|allocation| allocation.amount).sum::<f64>();
+        let mut current = 1;
+        while current < height {
+            let subsidy = self.scheduled_subsidy(current);
+            if subsidy == 0.0 {
+                break;
+            }
+            let epoch_end = match self.halving_interval {
+                0 => height,
+                interval => (current / interval + 1) * interval,
+            };
+            let blocks = epoch_end.min(height) - current;
+            supply += blocks as f64 * subsidy;
+            current += blocks;
+        }
+        supply
+    }
+
+    fn genesis_transactions(&self) -> Vec<Transaction> {
+        self.allocations
+            .iter()
+            .map(|allocation| Transaction::coinbase(&self.network_address, &allocation.address, allocation.amount, 0))
+            .collect()
+    }
+}
+
+impl Default for ChainSpec {
+    fn default() -> Self {
+        serde_json::from_str(include_str!("../chain_spec.json")).unwrap()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn genesis_hash_commits_to_block_reward() {
+        let spec = ChainSpec::default();
+        let other = ChainSpec { block_reward: spec.block_reward * 10.0, ..spec.clone() };
+        assert_ne!(spec.genesis_block().hash, other.genesis_block().hash);
+    }
+
+    #[test]
+    fn genesis_hash_commits_to_ledger() {
+        let spec = ChainSpec::default();
+        let ledger = match spec.ledger {
+            Ledger::Account => Ledger::Utxo,
+            Ledger::Utxo => Ledger::Account,
+        };
+        let other = ChainSpec { ledger, ..spec.clone() };
+        assert_ne!(spec.genesis_block().hash, other.genesis_block().hash);
+    }
+
+    #[test]
+    fn genesis_hash_does_not_depend_on_nonce_search() {
+        let spec = ChainSpec::default();
+        let mined = spec.mine_genesis_block();
+        assert_eq!(ChainSpec { genesis_nonce: mined.value, ..spec }.genesis_block().hash, mined.hash);
+    }
+
+    #[test]
+    fn default_genesis_meets_difficulty() {
+        let spec = ChainSpec::default();
+        assert!(spec.genesis_block().hash.starts_with(&"0".repeat(spec.difficulty)));
+    }
+
+    fn reward_spec(halving_interval: u64, max_supply: f64) -> ChainSpec {
+        ChainSpec { block_reward: 5.0, halving_interval, max_supply, ..ChainSpec::default() }
+    }
+
+    #[test]
+    fn subsidy_halves_on_interval_boundary() {
+        let spec = reward_spec(10, 1_000_000.0);
+        assert_eq!(spec.subsidy_at(0), 0.0);
+        assert_eq!(spec.subsidy_at(1), 5.0);
+        assert_eq!(spec.subsidy_at(9), 5.0);
+        assert_eq!(spec.subsidy_at(10), 2.5);
+        assert_eq!(spec.subsidy_at(19), 2.5);
+        assert_eq!(spec.subsidy_at(20), 1.25);
+        assert_eq!(reward_spec(1, 1_000_000.0).subsidy_at(64), 0.0);
+        assert_eq!(reward_spec(0, 1_000_000.0).subsidy_at(1_000), 5.0);
+    }
+
+    // 100 монет начального распределения и по 5 за первые два блока оставляют 2 монеты до предела
+    #[test]
+    fn subsidy_stops_at_max_supply() {
+        let spec = reward_spec(10, 112.0);
+        assert_eq!(spec.subsidy_at(2), 5.0);
+        assert_eq!(spec.subsidy_at(3), 2.0);
+        assert_eq!(spec.subsidy_at(4), 0.0);
+        assert_eq!(spec.subsidy_at(1_000), 0.0);
+
+        // предел во второй эпохе: блоки 1-9 по 5 монет и 10-14 по 2.5
+        let spec = reward_spec(10, 100.0 + 9.0 * 5.0 + 5.0 * 2.5 + 1.0);
+        assert_eq!(spec.subsidy_at(14), 2.5);
+        assert_eq!(spec.subsidy_at(15), 1.0);
+        assert_eq!(spec.subsidy_at(16), 0.0);
+    }
+}
@@ -0,0 +1,68 @@
+// шина событий узла: блокчейн публикует события при записи блока,
+// API раздает их подписчикам через WebSocket и SSE.
+// события мемпула и реорганизаций отложены: они появятся вместе с пулом неподтвержденных
+// транзакций и выбором ветви (см. README)
+
+use crate::blockchain::Balance;
+use serde::Serialize;
+use std::collections::HashSet;
+use tokio::sync::broadcast::{self, Receiver, Sender};
+
+// сколько событий ждут медленного подписчика, прежде чем он начнет их пропускать
+const EVENT_BUFFER: usize = 1024;
+
+#[derive(Clone, Debug, Serialize)]
+#[serde(tag = "type", rename_all = "snake_case")]
+pub enum Event {
+    // новая вершина цепочки
+    Tip { height: u64, hash: String, txids: Vec<String> },
+    // транзакция вошла в блок
+    Transaction { txid: String, height: u64, addresses: Vec<String> },
+    Balance { address: String, height: u64, balance: Balance },
+}
+
+impl Event {
+    pub fn name(&self) -> &'static str {
+        match self {
+            Event::Tip { .. } => "tip",
+            Event::Transaction { .. } => "transaction",
+            Event::Balance { .. } => "balance",
+        }
+    }
+
+    // при пустом фильтре подписчик получает все события, вершины приходят всегда
+    pub fn concerns(&self, addresses: &HashSet<String>) -> bool {
+        if addresses.is_empty() {
+            return true;
+        }
+        match self {
+            Event::Tip { .. } => true,
+            Event::Transaction { addresses: tx_addresses, .. } => {
+                tx_addresses.iter().any(|address| addresses.contains(address))
+            }
+            Event::Balance { address, .. } => addresses.contains(address),
+        }
+    }
+}
+
+#[derive(Clone)]
+pub struct EventBus {
+    sender: Sender<Event>,
+}
+
+impl Default for EventBus {
+    fn default() -> Self {
+        EventBus { sender: broadcast::channel(EVENT_BUFFER).0 }
+    }
+}
+
+impl EventBus {
+    pub fn publish(&self, event: Event) {
+        // без подписчиков событие просто теряется
+        let _ = self.sender.send(event);
+    }
+
+    pub fn subscribe(&self) -> Receiver<Event> {
+        self.sender.subscribe()
+    }
+}
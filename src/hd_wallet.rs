@@ -0,0 +1,176 @@
+// иерархический детерминированный кошелек (BIP32 на secp256k1)
+// seed получается из фразы BIP39, адреса - по пути m/44'/1'/0'/0/i
+
+use crate::keystore::{save_secret_encrypted, Kdf, SecretKind};
+use crate::wallet::Wallet;
+use crate::wallet_manager::WalletError;
+use bip39::Mnemonic;
+use hmac::{Hmac, Mac};
+use k256::ecdsa::SigningKey;
+use k256::elliptic_curve::rand_core::{OsRng, RngCore};
+use k256::elliptic_curve::PrimeField;
+use k256::{FieldBytes, NonZeroScalar, Scalar};
+use sha2::Sha512;
+use tracing::info;
+
+type HmacSha512 = Hmac<Sha512>;
+
+pub const SEED_LENGTH: usize = 64;
+
+const HARDENED: u32 = 0x8000_0000;
+const ACCOUNT_PATH: [u32; 4] = [44 | HARDENED, 1 | HARDENED, HARDENED, 0];
+
+pub struct HdWallet {
+    seed: Vec<u8>,
+}
+
+struct ExtendedKey {
+    key: SigningKey,
+    chain_code: [u8; 32],
+}
+
+impl HdWallet {
+    // возвращает кошелек и фразу из 12 слов для его восстановления
+    pub fn new() -> (Self, String) {
+        let mut entropy = [0u8; 16];
+        OsRng.fill_bytes(&mut entropy);
+        let mnemonic = Mnemonic::from_entropy(&entropy).unwrap();
+        let wallet = HdWallet::from_seed(&mnemonic.to_seed(""));
+        (wallet, mnemonic.to_string())
+    }
+
+    pub fn from_mnemonic(phrase: &str) -> Result<Self, bip39::Error> {
+        let mnemonic = Mnemonic::parse(phrase)?;
+        Ok(HdWallet::from_seed(&mnemonic.to_seed("")))
+    }
+
+    pub fn from_seed(seed: &[u8]) -> Self {
+        HdWallet { seed: seed.to_vec() }
+    }
+
+    // None, если на пути получился некорректный по BIP32 ключ (вероятность меньше 2^-127)
+    pub fn derive(&self, index: u32) -> Option<Wallet> {
+        let mut extended_key = ExtendedKey::master(&self.seed)?;
+        for child in ACCOUNT_PATH {
+            extended_key = extended_key.child(child)?;
+        }
+        Some(Wallet::from_signing_key(extended_key.child(index)?.key))
+    }
+
+    pub fn save_to_file_encrypted(&self, filename: &str, password: &str, prefix: &str, kdf: Kdf) -> Result<(), WalletError> {
+        let address = self.derive(0).ok_or(WalletError::InvalidKey)?.address(prefix);
+        save_secret_encrypted(filename, password, &self.seed, SecretKind::Seed, &address, kdf)?;
+        info!(file = filename, "HD-кошелек зашифрован и записан");
+        Ok(())
+    }
+}
+
+impl ExtendedKey {
+    fn master(seed: &[u8]) -> Option<Self> {
+        let mut mac = HmacSha512::new_from_slice(b"Bitcoin seed").unwrap();
+        mac.update(seed);
+        let hash = mac.finalize().into_bytes();
+
+        Some(ExtendedKey {
+            key: SigningKey::from_slice(&hash[..32]).ok()?,
+            chain_code: hash[32..].try_into().unwrap(),
+        })
+    }
+
+    fn child(&self, index: u32) -> Option<Self> {
+        let mut mac = HmacSha512::new_from_slice(&self.chain_code).unwrap();
+        if index >= HARDENED {
+            mac.update(&[0]);
+            mac.update(&self.key.to_bytes());
+        } else {
+            mac.update(&self.key.verifying_key().to_sec1_bytes());
+        }
+        mac.update(&index.to_be_bytes());
+        let hash = mac.finalize().into_bytes();
+
+        let tweak = Option::<Scalar>::from(Scalar::from_repr(*FieldBytes::from_slice(&hash[..32])))?;
+        let child = Option::<NonZeroScalar>::from(NonZeroScalar::new(tweak + self.key.as_nonzero_scalar().as_ref()))?;
+
+        Some(ExtendedKey {
+            key: SigningKey::from(child),
+            chain_code: hash[32..].try_into().unwrap(),
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // тестовый вектор 1 из BIP32: путь, chain code и закрытый ключ
+    const VECTOR_SEED: &str = "000102030405060708090a0b0c0d0e0f";
+    const VECTOR_KEYS: [(&[u32], &str, &str); 6] = [
+        (
+            &[],
+            "873dff81c02f525623fd1fe5167eac3a55a049de3d314bb42ee227ffed37d508",
+            "e8f32e723decf4051aefac8e2c93c9c5b214313817cdb01a1494b917c8436b35",
+        ),
+        (
+            &[HARDENED],
+            "47fdacbd0f1097043b78c63c20c34ef4ed9a111d980047ad16282c7ae6236141",
+            "edb2e14f9ee77d26dd93b4ecede8d16ed408ce149b6cd80b0715a2d911a0afea",
+        ),
+        (
+            &[HARDENED, 1],
+            "2a7857631386ba23dacac34180dd1983734e444fdbf774041578e9b6adb37c19",
+            "3c6cb8d0f6a264c91ea8b5030fadaa8e538b020f0a387421a12de9319dc93368",
+        ),
+        (
+            &[HARDENED, 1, 2 | HARDENED],
+            "04466b9cc8e161e966409ca52986c584f07e9dc81f735db683c3ff6ec7b1503f",
+            "cbce0d719ecf7431d88e6a89fa1483e02e35092af60c042b1df2ff59fa424dca",
+        ),
+        (
+            &[HARDENED, 1, 2 | HARDENED, 2],
+            "cfb71883f01676f587d023cc53a35bc7f88f724b1f8c2892ac1275ac822a3edd",
+            "0f479245fb19a38a1954c5c7c0ebab2f9bdfd96a17563ef28a6a4b1a2a764ef4",
+        ),
+        (
+            &[HARDENED, 1, 2 | HARDENED, 2, 1_000_000_000],
+            "c783e67b921d2beb8f6b389cc646d7263b4145701dadd2161548a8b078e65e9e",
+            "471b76e389e528d6de6d816857e012c5455051cad6660850e58372a6c3e6e7c8",
+        ),
+    ];
+
+    fn derive_path(seed: &[u8], path: &[u32]) -> ExtendedKey {
+        path.iter().fold(ExtendedKey::master(seed).unwrap(), |key, index| key.child(*index).unwrap())
+    }
+
+    #[test]
+    fn derivation_matches_bip32_test_vector() {
+        let seed = hex::decode(VECTOR_SEED).unwrap();
+        for (path, chain_code, private_key) in VECTOR_KEYS {
+            let key = derive_path(&seed, path);
+            assert_eq!(hex::encode(key.chain_code), chain_code, "chain code {:?}", path);
+            assert_eq!(hex::encode(key.key.to_bytes()), private_key, "закрытый ключ {:?}", path);
+        }
+    }
+
+    // фраза из тестовых векторов BIP39, seed получен без пароля фразы, как в кошельках узла
+    #[test]
+    fn mnemonic_seed_matches_bip39() {
+        let phrase = "abandon abandon abandon abandon abandon abandon abandon abandon abandon abandon abandon about";
+        let wallet = HdWallet::from_mnemonic(phrase).unwrap();
+        assert_eq!(
+            hex::encode(&wallet.seed),
+            "5eb00bbddcf069084889a8ab9155568165f5c453ccb85e70811aaed6f6da5fc1\
+             9a5ac40b389cd370d086206dec8aa6c43daea6690f20ad3d8d48b2d2ce9e38e4"
+        );
+    }
+
+    #[test]
+    fn wallet_addresses_follow_account_path() {
+        let seed = hex::decode(VECTOR_SEED).unwrap();
+        let wallet = HdWallet::from_seed(&seed);
+        for index in [0, 1, 7] {
+            let path = [ACCOUNT_PATH.as_slice(), &[index]].concat();
+            let expected = derive_path(&seed, &path).key;
+            assert_eq!(wallet.derive(index).unwrap().private_key.to_bytes(), expected.to_bytes());
+        }
+    }
+}
@@ -0,0 +1,665 @@
+// каталог сообщений для пользователя на английском и русском.
+// язык по умолчанию задается при запуске (--lang), API выбирает язык запроса по заголовку
+// Accept-Language. у каждого сообщения есть код, не зависящий от языка, - по нему ошибки
+// различают клиенты API
+
+use crate::blockchain::BlockError;
+use crate::partial_tx::PartialTxError;
+use crate::transaction::MAX_MULTISIG_KEYS;
+use crate::wallet_manager::{WalletError, MAX_NAME_LENGTH};
+use axum::extract::Request;
+use axum::http::header::{ACCEPT_LANGUAGE, CONTENT_LANGUAGE};
+use axum::http::HeaderValue;
+use axum::middleware::Next;
+use axum::response::Response;
+use std::fmt::{Display, Formatter};
+use std::process::exit;
+use std::sync::OnceLock;
+
+#[derive(Clone, Copy, Debug, Default, PartialEq, clap::ValueEnum)]
+pub enum Lang {
+    En,
+    #[default]
+    Ru,
+}
+
+static DEFAULT_LANG: OnceLock<Lang> = OnceLock::new();
+
+tokio::task_local! {
+    // язык запроса к API, действует на время его обработки
+    static REQUEST_LANG: Lang;
+}
+
+pub fn set_default_lang(lang: Lang) {
+    DEFAULT_LANG.set(lang).unwrap();
+}
+
+// язык текущего запроса к API, вне запроса - язык по умолчанию
+pub fn lang() -> Lang {
+    REQUEST_LANG
+        .try_with(|lang| *lang)
+        .unwrap_or_else(|_| DEFAULT_LANG.get().copied().unwrap_or_default())
+}
+
+// ошибка, после которой команда или узел не могут продолжать работу
+pub fn fail(message: impl Display) -> ! {
+    eprintln!("{}", message);
+    exit(1);
+}
+
+impl Lang {
+    pub fn tag(&self) -> &'static str {
+        match self {
+            Lang::En => "en",
+            Lang::Ru => "ru",
+        }
+    }
+
+    // язык с наибольшим весом q среди поддерживаемых, при равных весах - первый в списке
+    pub fn from_accept_language(header: &str) -> Option<Lang> {
+        let mut best: Option<(Lang, f32)> = None;
+        for item in header.split(',') {
+            let mut parts = item.split(';');
+            let tag = parts.next().unwrap_or_default().trim().to_ascii_lowercase();
+            let lang = match tag.split('-').next().unwrap_or_default() {
+                "en" => Lang::En,
+                "ru" => Lang::Ru,
+                _ => continue,
+            };
+            let q = parts
+                .find_map(|param| param.trim().strip_prefix("q="))
+                .map(|q| q.parse().unwrap_or(0.0))
+                .unwrap_or(1.0);
+            if q > 0.0 && best.is_none_or(|(_, best_q)| q > best_q) {
+                best = Some((lang, q));
+            }
+        }
+        best.map(|(lang, _)| lang)
+    }
+}
+
+// выбирает язык запроса; без подходящего Accept-Language используется язык узла
+pub async fn localize(request: Request, next: Next) -> Response {
+    let lang = request
+        .headers()
+        .get(ACCEPT_LANGUAGE)
+        .and_then(|value| value.to_str().ok())
+        .and_then(Lang::from_accept_language)
+        .unwrap_or_else(lang);
+    let mut response = REQUEST_LANG.scope(lang, next.run(request)).await;
+    response.headers_mut().insert(CONTENT_LANGUAGE, HeaderValue::from_static(lang.tag()));
+    response
+}
+
+pub struct Entry {
+    code: &'static str,
+    en: String,
+    ru: String,
+}
+
+fn entry(code: &'static str, en: impl Into<String>, ru: impl Into<String>) -> Entry {
+    Entry { code, en: en.into(), ru: ru.into() }
+}
+
+pub trait Localize {
+    fn entry(&self) -> Entry;
+
+    fn code(&self) -> &'static str {
+        self.entry().code
+    }
+
+    fn text(&self, lang: Lang) -> String {
+        let entry = self.entry();
+        match lang {
+            Lang::En => entry.en,
+            Lang::Ru => entry.ru,
+        }
+    }
+}
+
+// сообщения API и командной строки, не связанные с ошибками отдельных модулей
+pub enum Message {
+    InvalidAddress,
+    InvalidAddressValue(String),
+    InvalidRecipient,
+    InvalidSender,
+    BlockNotFound,
+    TransactionNotFound,
+    TransactionRejected(BlockError),
+    TransactionAdded,
+    NothingToCombine,
+    TransactionsMismatch,
+    MultisigNotFound,
+    WatchWalletCreated,
+    WalletUnlocked(u64),
+    WalletLocked,
+    PasswordChanged,
+    Unauthorized,
+    Forbidden,
+    PayloadTooLarge,
+    TooManyRequests,
+    NodeBusy,
+    InvalidJson(String),
+    InvalidBody(String),
+    UnsupportedContentType,
+    InvalidPath(String),
+    InvalidQuery(String),
+    InvalidRpcRequest,
+    EmptyBatch,
+    BatchTooLarge(usize),
+    UnknownMethod(String),
+    InvalidParams(String),
+    // запуск узла
+    InvalidLogFilter(String),
+    InvalidApiKeysFile(String),
+    InvalidChainSpec(String),
+    InvalidTls(String),
+    InvalidCorsOrigin(String),
+    InvalidRateLimit,
+    DatabaseOpenFailed(String, String),
+    LedgerMismatch(String, String),
+    IncompatibleDatabase(String),
+    GenesisNotMined,
+    GenesisMismatch(String, String),
+    P2pBindFailed(String, String),
+    GenesisNonce(u64),
+    GenesisHash(String),
+    // командная строка
+    PromptPassword,
+    PromptCurrentPassword,
+    PromptNewPassword,
+    PromptRepeatPassword,
+    PromptPrivateKey,
+    PromptWalletPassword,
+    PasswordsMismatch,
+    FileExists(String),
+    OpenFailed(String, String),
+    WriteFailed(String, String),
+    InvalidSignatureFile(String),
+    SignatureValid(String),
+    SignatureInvalid,
+    WalletAddress(String),
+    TransactionSigned,
+    ReadyToSend(bool),
+    NodeUnavailable(String),
+    // сводка файла транзакции
+    TxChain(String, String),
+    TxFrom(String),
+    TxTo(String),
+    TxAmount(f64),
+    TxFee(f64),
+    TxSignatureScheme(String),
+    TxNonce(u64),
+    TxInputs(usize, f64),
+    TxOutput(f64, String),
+    TxMultisigSignatures(usize, usize, u8),
+    TxSigned(bool),
+}
+
+impl Display for Message {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{}", self.text(lang()))
+    }
+}
+
+fn yes_no(value: bool) -> (&'static str, &'static str) {
+    if value {
+        ("yes", "да")
+    } else {
+        ("no", "нет")
+    }
+}
+
+impl Localize for Message {
+    fn entry(&self) -> Entry {
+        match self {
+            Message::InvalidAddress => entry("invalid_address", "Invalid address", "Некорректный адрес"),
+            Message::InvalidAddressValue(address) => entry(
+                "invalid_address",
+                format!("Invalid address {}", address),
+                format!("Некорректный адрес {}", address),
+            ),
+            Message::InvalidRecipient => entry("invalid_recipient", "Invalid recipient address", "Некорректный адрес получателя"),
+            Message::InvalidSender => entry("invalid_sender", "Invalid sender address", "Некорректный адрес отправителя"),
+            Message::BlockNotFound => entry("block_not_found", "Block not found", "Блок не найден"),
+            Message::TransactionNotFound => entry("transaction_not_found", "Transaction not found", "Транзакция не найдена"),
+            // код - причина отказа
+            Message::TransactionRejected(e) => entry(
+                e.code(),
+                format!("Transaction rejected: {}", e.text(Lang::En)),
+                format!("Транзакция отклонена: {}", e.text(Lang::Ru)),
+            ),
+            Message::TransactionAdded => entry("transaction_added", "Transaction added", "Транзакция успешно добавлена"),
+            Message::NothingToCombine => entry("nothing_to_combine", "No transactions to combine", "Нет транзакций для объединения"),
+            Message::TransactionsMismatch => entry("transactions_mismatch", "Transactions do not match", "Транзакции не совпадают"),
+            Message::MultisigNotFound => entry(
+                "multisig_not_found",
+                "Multisig address not found in the wallet directory",
+                "Мультиподписной адрес не найден в каталоге кошельков",
+            ),
+            Message::WatchWalletCreated => entry("watch_wallet_created", "Watch-only wallet created", "Кошелек наблюдения создан"),
+            Message::WalletUnlocked(seconds) => entry(
+                "wallet_unlocked",
+                format!("Wallet unlocked for {} s", seconds),
+                format!("Кошелек разблокирован на {} с", seconds),
+            ),
+            Message::WalletLocked => entry("wallet_locked", "Wallet locked", "Кошелек заблокирован"),
+            Message::PasswordChanged => entry("password_changed", "Wallet password changed", "Пароль кошелька изменен"),
+            Message::Unauthorized => entry("unauthorized", "API key required", "Требуется ключ доступа к API"),
+            Message::Forbidden => entry("forbidden", "Not allowed to perform this operation", "Недостаточно прав для этой операции"),
+            Message::PayloadTooLarge => entry("payload_too_large", "Request is too large", "Слишком большой запрос"),
+            Message::TooManyRequests => entry(
+                "too_many_requests",
+                "Too many requests, try again later",
+                "Слишком много запросов, повторите позже",
+            ),
+            Message::NodeBusy => entry("node_busy", "Node is busy, try again later", "Узел занят, повторите позже"),
+            Message::InvalidJson(e) => entry("invalid_json", format!("Invalid JSON: {}", e), format!("Некорректный JSON: {}", e)),
+            Message::InvalidBody(e) => entry(
+                "invalid_body",
+                format!("Invalid request body: {}", e),
+                format!("Некорректное тело запроса: {}", e),
+            ),
+            Message::UnsupportedContentType => entry(
+                "unsupported_content_type",
+                "Expected a request with Content-Type: application/json",
+                "Ожидается запрос с Content-Type: application/json",
+            ),
+            Message::InvalidPath(e) => entry(
+                "invalid_path",
+                format!("Invalid URL path: {}", e),
+                format!("Некорректный путь запроса: {}", e),
+            ),
+            Message::InvalidQuery(e) => entry(
+                "invalid_query",
+                format!("Invalid query parameters: {}", e),
+                format!("Некорректные параметры запроса: {}", e),
+            ),
+            Message::InvalidRpcRequest => entry("invalid_request", "Invalid JSON-RPC request", "Некорректный запрос JSON-RPC"),
+            Message::EmptyBatch => entry("empty_batch", "Empty request batch", "Пустой пакет запросов"),
+            Message::BatchTooLarge(limit) => entry(
+                "batch_too_large",
+                format!("A batch may contain at most {} requests", limit),
+                format!("В пакете может быть не больше {} запросов", limit),
+            ),
+            Message::UnknownMethod(method) => entry(
+                "unknown_method",
+                format!("Unknown method {}", method),
+                format!("Неизвестный метод {}", method),
+            ),
+            Message::InvalidParams(e) => entry(
+                "invalid_params",
+                format!("Invalid parameters: {}", e),
+                format!("Некорректные параметры: {}", e),
+            ),
+            Message::InvalidLogFilter(e) => entry(
+                "invalid_log_filter",
+                format!("Invalid log filter: {}", e),
+                format!("Некорректный фильтр журнала: {}", e),
+            ),
+            Message::InvalidApiKeysFile(e) => entry(
+                "invalid_api_keys_file",
+                format!("Invalid API keys file: {}", e),
+                format!("Некорректный файл ключей API: {}", e),
+            ),
+            Message::InvalidChainSpec(e) => entry(
+                "invalid_chain_spec",
+                format!("Invalid chain specification: {}", e),
+                format!("Некорректная спецификация цепочки: {}", e),
+            ),
+            Message::InvalidTls(e) => entry(
+                "invalid_tls",
+                format!("Failed to load TLS certificate and key: {}", e),
+                format!("Не удалось загрузить сертификат и ключ TLS: {}", e),
+            ),
+            Message::InvalidCorsOrigin(origin) => entry(
+                "invalid_cors_origin",
+                format!("Invalid CORS origin {}", origin),
+                format!("Некорректный источник CORS {}", origin),
+            ),
+            Message::InvalidRateLimit => entry(
+                "invalid_rate_limit",
+                "--rate-limit and --rate-burst must be greater than zero",
+                "--rate-limit и --rate-burst должны быть больше нуля",
+            ),
+            Message::DatabaseOpenFailed(path, e) => entry(
+                "database_open_failed",
+                format!("Failed to open database {}: {}", path, e),
+                format!("Не удалось открыть базу {}: {}", path, e),
+            ),
+            Message::LedgerMismatch(stored, spec) => entry(
+                "ledger_mismatch",
+                format!("The chain was created in {} mode, but the specification sets {} mode", stored, spec),
+                format!("Цепочка создана в режиме {}, а спецификация задает режим {}", stored, spec),
+            ),
+            Message::IncompatibleDatabase(path) => entry(
+                "incompatible_database",
+                format!(
+                    "Database {} was created by an old node version and is incompatible with the current block format, \
+                     delete it and start the node again",
+                    path
+                ),
+                format!(
+                    "База {} создана старой версией узла и несовместима с текущим форматом блоков, \
+                     удалите ее и запустите узел заново",
+                    path
+                ),
+            ),
+            Message::GenesisNotMined => entry(
+                "genesis_not_mined",
+                "The genesis block from the specification does not meet the difficulty, find genesis_nonce with the genesis command",
+                "Первый блок из спецификации не удовлетворяет сложности, подберите genesis_nonce командой genesis",
+            ),
+            Message::GenesisMismatch(stored, spec) => entry(
+                "genesis_mismatch",
+                format!("The genesis block in the database {} differs from the genesis block of the specification {}", stored, spec),
+                format!("Первый блок в базе {} не совпадает с первым блоком из спецификации {}", stored, spec),
+            ),
+            Message::P2pBindFailed(addr, e) => entry(
+                "p2p_bind_failed",
+                format!("Failed to start the P2P server on {}: {}", addr, e),
+                format!("Не удалось запустить P2P-сервер на {}: {}", addr, e),
+            ),
+            // имя поля спецификации не переводится
+            Message::GenesisNonce(nonce) => entry(
+                "genesis_nonce",
+                format!("genesis_nonce: {}", nonce),
+                format!("genesis_nonce: {}", nonce),
+            ),
+            Message::GenesisHash(hash) => entry(
+                "genesis_hash",
+                format!("Genesis block hash: {}", hash),
+                format!("Хеш первого блока: {}", hash),
+            ),
+            Message::PromptPassword => entry("prompt_password", "Password: ", "Пароль: "),
+            Message::PromptCurrentPassword => entry("prompt_current_password", "Current password: ", "Текущий пароль: "),
+            Message::PromptNewPassword => entry("prompt_new_password", "New password: ", "Новый пароль: "),
+            Message::PromptRepeatPassword => entry("prompt_repeat_password", "Repeat new password: ", "Повторите новый пароль: "),
+            Message::PromptPrivateKey => entry("prompt_private_key", "Private key (hex or WIF): ", "Закрытый ключ (hex или WIF): "),
+            Message::PromptWalletPassword => entry("prompt_wallet_password", "Wallet password: ", "Пароль кошелька: "),
+            Message::PasswordsMismatch => entry("passwords_mismatch", "Passwords do not match", "Пароли не совпадают"),
+            Message::FileExists(file) => entry(
+                "file_exists",
+                format!("File {} already exists", file),
+                format!("Файл {} уже существует", file),
+            ),
+            Message::OpenFailed(file, e) => entry(
+                "open_failed",
+                format!("Failed to open file {}: {}", file, e),
+                format!("Не удалось открыть файл {}: {}", file, e),
+            ),
+            Message::WriteFailed(file, e) => entry(
+                "write_failed",
+                format!("Failed to write file {}: {}", file, e),
+                format!("Не удалось записать файл {}: {}", file, e),
+            ),
+            Message::InvalidSignatureFile(e) => entry(
+                "invalid_signature_file",
+                format!("Invalid signature file: {}", e),
+                format!("Некорректный файл подписи: {}", e),
+            ),
+            Message::SignatureValid(address) => entry(
+                "signature_valid",
+                format!("Signature is valid, the message was signed by the owner of {}", address),
+                format!("Подпись действительна, сообщение подписано владельцем адреса {}", address),
+            ),
+            Message::SignatureInvalid => entry("signature_invalid", "Signature is invalid", "Подпись недействительна"),
+            Message::WalletAddress(address) => entry(
+                "wallet_address",
+                format!("Wallet address {}", address),
+                format!("Адрес кошелька {}", address),
+            ),
+            Message::TransactionSigned => entry("transaction_signed", "Transaction signed", "Транзакция подписана"),
+            Message::ReadyToSend(ready) => {
+                let (en, ru) = yes_no(*ready);
+                entry("ready_to_send", format!("Ready to send: {}", en), format!("Готова к отправке: {}", ru))
+            }
+            Message::NodeUnavailable(e) => entry(
+                "node_unavailable",
+                format!("Node is unavailable: {}", e),
+                format!("Узел недоступен: {}", e),
+            ),
+            Message::TxChain(network_id, genesis_hash) => entry(
+                "tx_chain",
+                format!("Chain: {} ({})", network_id, genesis_hash),
+                format!("Цепочка: {} ({})", network_id, genesis_hash),
+            ),
+            Message::TxFrom(address) => entry("tx_from", format!("From: {}", address), format!("Отправитель: {}", address)),
+            Message::TxTo(address) => entry("tx_to", format!("To: {}", address), format!("Получатель: {}", address)),
+            Message::TxAmount(amount) => entry("tx_amount", format!("Amount: {}", amount), format!("Сумма: {}", amount)),
+            Message::TxFee(fee) => entry("tx_fee", format!("Fee: {}", fee), format!("Комиссия: {}", fee)),
+            Message::TxSignatureScheme(scheme) => entry(
+                "tx_signature_scheme",
+                format!("Signature scheme: {}", scheme),
+                format!("Схема подписи: {}", scheme),
+            ),
+            Message::TxNonce(nonce) => entry(
+                "tx_nonce",
+                format!("Sender transaction number: {}", nonce),
+                format!("Номер транзакции отправителя: {}", nonce),
+            ),
+            Message::TxInputs(count, total) => entry(
+                "tx_inputs",
+                format!("Inputs: {} totalling {}", count, total),
+                format!("Входы: {} на сумму {}", count, total),
+            ),
+            Message::TxOutput(amount, address) => entry(
+                "tx_output",
+                format!("Output: {} -> {}", amount, address),
+                format!("Выход: {} -> {}", amount, address),
+            ),
+            Message::TxMultisigSignatures(count, keys, threshold) => entry(
+                "tx_multisig_signatures",
+                format!("Signatures: {} of {} ({} required)", count, keys, threshold),
+                format!("Подписи: {} из {} (нужно {})", count, keys, threshold),
+            ),
+            Message::TxSigned(signed) => {
+                let (en, ru) = yes_no(*signed);
+                entry("tx_signed", format!("Signed: {}", en), format!("Подписана: {}", ru))
+            }
+        }
+    }
+}
+
+impl Localize for BlockError {
+    fn entry(&self) -> Entry {
+        match self {
+            BlockError::InvalidIndex => entry("invalid_index", "invalid block height", "неверный номер блока"),
+            BlockError::InvalidPrevHash => entry(
+                "invalid_prev_hash",
+                "block does not reference the chain tip",
+                "блок не ссылается на последний блок цепочки",
+            ),
+            BlockError::InvalidHash => entry(
+                "invalid_hash",
+                "block hash does not match its contents",
+                "хеш блока не совпадает с содержимым",
+            ),
+            BlockError::InsufficientWork => entry(
+                "insufficient_work",
+                "block hash does not meet the difficulty",
+                "хеш блока не удовлетворяет сложности",
+            ),
+            BlockError::InvalidTimestamp => entry("invalid_timestamp", "invalid block time", "неверное время блока"),
+            BlockError::MissingCoinbase => entry(
+                "missing_coinbase",
+                "the first transaction of a block must be the miner reward",
+                "первой транзакцией блока должна быть награда майнеру",
+            ),
+            BlockError::UnexpectedCoinbase => entry(
+                "unexpected_coinbase",
+                "block has more than one network transaction",
+                "в блоке больше одной транзакции от сети",
+            ),
+            BlockError::InvalidReward => entry("invalid_reward", "invalid miner reward", "неверная сумма награды майнеру"),
+            BlockError::InvalidAmount => entry("invalid_amount", "invalid transaction amount", "неверная сумма транзакции"),
+            BlockError::InvalidAddress => entry("invalid_address", "invalid address", "некорректный адрес"),
+            BlockError::InsufficientFee => entry(
+                "insufficient_fee",
+                "transaction fee is below the minimum",
+                "комиссия транзакции ниже минимальной",
+            ),
+            BlockError::InvalidSignature => entry("invalid_signature", "invalid transaction signature", "неверная подпись транзакции"),
+            BlockError::DuplicateTransaction => entry(
+                "duplicate_transaction",
+                "transaction is repeated in the block",
+                "транзакция повторяется в блоке",
+            ),
+            BlockError::InsufficientFunds => entry("insufficient_funds", "insufficient funds", "недостаточно средств"),
+            BlockError::LedgerMismatch => entry(
+                "ledger_mismatch",
+                "transaction does not match the chain ledger mode",
+                "транзакция не соответствует режиму учета цепочки",
+            ),
+            BlockError::UnknownInput => entry(
+                "unknown_input",
+                "transaction input is not among the sender's unspent outputs",
+                "вход транзакции не найден среди непотраченных выходов отправителя",
+            ),
+            BlockError::DoubleSpend => entry("double_spend", "output is spent twice in the block", "выход тратится в блоке повторно"),
+            BlockError::InvalidFee => entry(
+                "invalid_fee",
+                "inputs do not equal outputs plus fee",
+                "сумма входов не равна сумме выходов с комиссией",
+            ),
+            BlockError::ImmatureCoinbase => entry(
+                "immature_coinbase",
+                "miner reward cannot be spent yet",
+                "награда майнеру еще не может быть потрачена",
+            ),
+            BlockError::InvalidNonce => entry(
+                "invalid_nonce",
+                "invalid sender transaction number",
+                "неверный порядковый номер транзакции отправителя",
+            ),
+        }
+    }
+}
+
+impl Localize for WalletError {
+    fn entry(&self) -> Entry {
+        match self {
+            WalletError::InvalidName => entry(
+                "invalid_wallet_name",
+                format!(
+                    "Wallet name may contain only latin letters, digits, '-' and '_' (up to {} characters)",
+                    MAX_NAME_LENGTH
+                ),
+                format!(
+                    "Имя кошелька может содержать только латинские буквы, цифры, '-' и '_' (до {} символов)",
+                    MAX_NAME_LENGTH
+                ),
+            ),
+            WalletError::NotFound => entry("wallet_not_found", "Wallet not found", "Кошелек не найден"),
+            WalletError::AlreadyExists => entry("wallet_exists", "A wallet with this name already exists", "Кошелек с таким именем уже существует"),
+            WalletError::WrongPassword => entry("wrong_password", "Wrong password", "Неверный пароль"),
+            WalletError::UnreadableFile(e) => entry(
+                "unreadable_wallet_file",
+                format!("Failed to open wallet file: {}", e),
+                format!("Не удалось открыть файл кошелька: {}", e),
+            ),
+            WalletError::InvalidFile(e) => entry(
+                "invalid_wallet_file",
+                format!("Invalid wallet file: {}", e),
+                format!("Некорректный файл кошелька: {}", e),
+            ),
+            WalletError::WriteFailed(e) => entry(
+                "wallet_write_failed",
+                format!("Failed to write wallet files: {}", e),
+                format!("Не удалось записать файлы кошелька: {}", e),
+            ),
+            WalletError::UnsupportedVersion(version) => entry(
+                "unsupported_wallet_version",
+                format!("Unsupported wallet file version {}", version),
+                format!("Неподдерживаемая версия файла кошелька {}", version),
+            ),
+            // код отличается от ответа на успешную блокировку
+            WalletError::Locked => entry("wallet_not_unlocked", "Wallet is locked", "Кошелек заблокирован"),
+            WalletError::UnknownAccount(index) => entry(
+                "unknown_account",
+                format!("Wallet has no address number {}", index),
+                format!("В кошельке нет адреса с номером {}", index),
+            ),
+            WalletError::SingleAccount => entry(
+                "single_account_wallet",
+                "A single-key wallet does not support additional addresses",
+                "Кошелек из одного ключа не поддерживает дополнительные адреса",
+            ),
+            WalletError::InvalidMnemonic(e) => entry(
+                "invalid_mnemonic",
+                format!("Invalid recovery phrase: {}", e),
+                format!("Некорректная фраза восстановления: {}", e),
+            ),
+            WalletError::InvalidKey => entry("invalid_private_key", "Invalid private key", "Некорректный закрытый ключ"),
+            WalletError::NoPrivateKeys => entry("no_private_keys", "Wallet has no private keys", "У кошелька нет закрытых ключей"),
+            WalletError::NotWatchOnly => entry(
+                "not_watch_only",
+                "Addresses without keys can only be added to a watch-only wallet",
+                "Адреса без ключей добавляются только в кошелек наблюдения",
+            ),
+            WalletError::InvalidWatchTarget => entry(
+                "invalid_watch_target",
+                "Expected an address or a hex public key",
+                "Ожидается адрес или открытый ключ в hex",
+            ),
+            WalletError::DuplicateAddress => entry("duplicate_address", "Address is already in the wallet", "Адрес уже есть в кошельке"),
+            WalletError::InvalidPublicKey => entry("invalid_public_key", "Invalid public key", "Некорректный открытый ключ"),
+            WalletError::InvalidMultisig => entry(
+                "invalid_multisig",
+                format!(
+                    "Multisig needs from 1 to {} distinct keys and a threshold not above their number",
+                    MAX_MULTISIG_KEYS
+                ),
+                format!(
+                    "Для мультиподписи нужно от 1 до {} разных ключей и порог не больше их числа",
+                    MAX_MULTISIG_KEYS
+                ),
+            ),
+            WalletError::NotMultisig => entry("not_multisig", "Wallet is not a multisig wallet", "Кошелек не является мультиподписным"),
+            WalletError::NotMultisigTransaction => entry(
+                "not_multisig_transaction",
+                "Transaction does not use multisig",
+                "Транзакция не использует мультиподпись",
+            ),
+            WalletError::KeyNotInMultisig => entry(
+                "key_not_in_multisig",
+                "The key of this address is not among the multisig keys",
+                "Ключа этого адреса нет среди ключей мультиподписи",
+            ),
+        }
+    }
+}
+
+impl Localize for PartialTxError {
+    fn entry(&self) -> Entry {
+        match self {
+            PartialTxError::InvalidFormat(e) => entry(
+                "invalid_tx_file",
+                format!("Invalid transaction file: {}", e),
+                format!("Некорректный файл транзакции: {}", e),
+            ),
+            PartialTxError::UnsupportedVersion(version) => entry(
+                "unsupported_tx_version",
+                format!("Unsupported transaction file version {}", version),
+                format!("Неподдерживаемая версия файла транзакции {}", version),
+            ),
+            PartialTxError::WrongNetwork => entry(
+                "wrong_network",
+                "Transaction was built for another chain",
+                "Транзакция собрана для другой цепочки",
+            ),
+            PartialTxError::WrongSigner => entry(
+                "wrong_signer",
+                "Key does not match the sender address",
+                "Ключ не соответствует адресу отправителя",
+            ),
+            PartialTxError::KeyNotInMultisig => entry(
+                "key_not_in_multisig",
+                "Key is not among the multisig keys",
+                "Ключа нет среди ключей мультиподписи",
+            ),
+            PartialTxError::Mismatch => entry("tx_mismatch", "Files contain different transactions", "Файлы содержат разные транзакции"),
+            PartialTxError::Incomplete => entry("tx_incomplete", "Transaction is not fully signed", "Транзакция подписана не полностью"),
+        }
+    }
+}
@@ -0,0 +1,209 @@
+// файл кошелька: JSON с версией формата, параметрами KDF и шифра и адресом.
+// старые файлы из трех строк base64 (PBKDF2, 100 000 итераций) тоже читаются
+
+use crate::wallet_manager::WalletError;
+use argon2::Argon2;
+use base64::{engine::general_purpose, Engine as _};
+use chacha20poly1305::aead::generic_array::GenericArray;
+use chacha20poly1305::aead::Aead;
+use chacha20poly1305::{ChaCha20Poly1305, KeyInit};
+use k256::elliptic_curve::rand_core::{OsRng, RngCore};
+use pbkdf2::pbkdf2_hmac;
+use serde::{Deserialize, Serialize};
+use sha2::Sha256;
+use std::fs::{read_to_string, rename, write};
+
+const KEYSTORE_VERSION: u32 = 2;
+const CIPHER: &str = "chacha20poly1305";
+const LEGACY_PBKDF2_ITERATIONS: u32 = 100_000;
+// 1 ГиБ
+const MAX_ARGON2_MEMORY_KIB: u32 = 1 << 20;
+
+#[derive(Serialize, Deserialize)]
+pub struct Keystore {
+    pub version: u32,
+    pub kind: SecretKind,
+    pub address: String,
+    pub kdf: Kdf,
+    pub salt: String,
+    pub cipher: String,
+    pub nonce: String,
+    pub ciphertext: String,
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum SecretKind {
+    Key,
+    Seed,
+}
+
+#[derive(Clone, Copy, Debug, Default, Deserialize, clap::ValueEnum)]
+#[serde(rename_all = "lowercase")]
+pub enum KdfKind {
+    Pbkdf2,
+    Scrypt,
+    #[default]
+    Argon2,
+}
+
+#[derive(Clone, Debug, Serialize, Deserialize)]
+#[serde(tag = "name", rename_all = "lowercase")]
+pub enum Kdf {
+    Pbkdf2 { iterations: u32 },
+    Scrypt { log_n: u8, r: u32, p: u32 },
+    Argon2 { memory_kib: u32, iterations: u32, parallelism: u32 },
+}
+
+impl Kdf {
+    pub fn recommended(kind: KdfKind) -> Self {
+        match kind {
+            KdfKind::Pbkdf2 => Kdf::Pbkdf2 { iterations: 600_000 },
+            KdfKind::Scrypt => Kdf::Scrypt { log_n: 15, r: 8, p: 1 },
+            KdfKind::Argon2 => Kdf::Argon2 { memory_kib: 19_456, iterations: 2, parallelism: 1 },
+        }
+    }
+
+    // параметры читаются из файла кошелька, поэтому ограничены: слишком большие заняли бы
+    // память и процессор узла, слишком маленькие не защищают пароль
+    fn check(&self) -> Result<(), WalletError> {
+        let valid = match *self {
+            Kdf::Pbkdf2 { iterations } => (1_000..=10_000_000).contains(&iterations),
+            Kdf::Scrypt { log_n, r, p } => (10..=20).contains(&log_n) && (1..=16).contains(&r) && (1..=4).contains(&p),
+            Kdf::Argon2 { memory_kib, iterations, parallelism } => {
+                (1..=4).contains(&parallelism)
+                    && (8 * parallelism..=MAX_ARGON2_MEMORY_KIB).contains(&memory_kib)
+                    && (1..=10).contains(&iterations)
+            }
+        };
+        if !valid {
+            return Err(WalletError::InvalidFile(format!("{:?}", self)));
+        }
+        Ok(())
+    }
+
+    fn derive_key(&self, password: &str, salt: &[u8]) -> Result<[u8; 32], WalletError> {
+        let mut key = [0u8; 32];
+        match *self {
+            Kdf::Pbkdf2 { iterations } => {
+                pbkdf2_hmac::<Sha256>(password.as_bytes(), salt, iterations, &mut key);
+            }
+            Kdf::Scrypt { log_n, r, p } => {
+                let params = scrypt::Params::new(log_n, r, p, key.len()).map_err(|e| WalletError::InvalidFile(e.to_string()))?;
+                scrypt::scrypt(password.as_bytes(), salt, &params, &mut key).map_err(|e| WalletError::InvalidFile(e.to_string()))?;
+            }
+            Kdf::Argon2 { memory_kib, iterations, parallelism } => {
+                let params = argon2::Params::new(memory_kib, iterations, parallelism, Some(key.len()))
+                    .map_err(|e| WalletError::InvalidFile(e.to_string()))?;
+                Argon2::new(argon2::Algorithm::Argon2id, argon2::Version::V0x13, params)
+                    .hash_password_into(password.as_bytes(), salt, &mut key)
+                    .map_err(|e| WalletError::InvalidFile(e.to_string()))?;
+            }
+        }
+        Ok(key)
+    }
+}
+
+// секрет (ключ или seed) шифруется ключом, полученным из пароля
+pub fn save_secret_encrypted(
+    filename: &str,
+    password: &str,
+    secret: &[u8],
+    kind: SecretKind,
+    address: &str,
+    kdf: Kdf,
+) -> Result<(), WalletError> {
+    let mut salt = [0u8; 16];
+    let mut nonce = [0u8; 12];
+    OsRng.fill_bytes(&mut salt);
+    OsRng.fill_bytes(&mut nonce);
+
+    let key = kdf.derive_key(password, &salt)?;
+    let cipher = ChaCha20Poly1305::new(GenericArray::from_slice(&key));
+    // шифр отказывает только на данных длиннее 256 ГиБ
+    let cipher_text = cipher.encrypt(GenericArray::from_slice(&nonce), secret).unwrap();
+
+    let keystore = Keystore {
+        version: KEYSTORE_VERSION,
+        kind,
+        address: address.to_string(),
+        kdf,
+        salt: general_purpose::STANDARD.encode(salt),
+        cipher: CIPHER.to_string(),
+        nonce: general_purpose::STANDARD.encode(nonce),
+        ciphertext: general_purpose::STANDARD.encode(cipher_text),
+    };
+    // файл заменяется целиком, чтобы при сбое не потерять старый кошелек
+    let tmp_filename = format!("{}.tmp", filename);
+    let write_failed = |e: std::io::Error| WalletError::WriteFailed(format!("{}: {}", filename, e));
+    write(&tmp_filename, serde_json::to_string_pretty(&keystore).unwrap()).map_err(write_failed)?;
+    rename(&tmp_filename, filename).map_err(write_failed)
+}
+
+pub fn load_secret_encrypted(filename: &str, password: &str) -> Result<Vec<u8>, WalletError> {
+    let file_content = read_to_string(filename).map_err(|e| WalletError::UnreadableFile(e.to_string()))?;
+    let decode = |field: &str| general_purpose::STANDARD.decode(field).map_err(|e| WalletError::InvalidFile(e.to_string()));
+
+    let (kdf, salt, nonce, cipher_text) = if file_content.trim_start().starts_with('{') {
+        let keystore: Keystore = serde_json::from_str(&file_content).map_err(|e| WalletError::InvalidFile(e.to_string()))?;
+        if keystore.version != KEYSTORE_VERSION || keystore.cipher != CIPHER {
+            return Err(WalletError::UnsupportedVersion(keystore.version));
+        }
+        (keystore.kdf, decode(&keystore.salt)?, decode(&keystore.nonce)?, decode(&keystore.ciphertext)?)
+    } else {
+        let mut lines = file_content.lines();
+        let mut next = || decode(lines.next().unwrap_or_default());
+        (Kdf::Pbkdf2 { iterations: LEGACY_PBKDF2_ITERATIONS }, next()?, next()?, next()?)
+    };
+    kdf.check()?;
+    if nonce.len() != 12 {
+        return Err(WalletError::InvalidFile(format!("nonce length {}", nonce.len())));
+    }
+
+    let key = kdf.derive_key(password, &salt)?;
+    let cipher = ChaCha20Poly1305::new(GenericArray::from_slice(&key));
+    cipher
+        .decrypt(GenericArray::from_slice(&nonce), &cipher_text[..])
+        .map_err(|_| WalletError::WrongPassword)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn keystore_file(name: &str, kdf: Kdf) -> String {
+        let filename = std::env::temp_dir().join(name).to_str().unwrap().to_string();
+        save_secret_encrypted(&filename, "p", &[7; 32], SecretKind::Key, "address", kdf).unwrap();
+        filename
+    }
+
+    // подменяет параметры KDF в уже записанном файле
+    fn tamper(filename: &str, kdf: Kdf) {
+        let mut keystore: Keystore = serde_json::from_str(&read_to_string(filename).unwrap()).unwrap();
+        keystore.kdf = kdf;
+        write(filename, serde_json::to_string(&keystore).unwrap()).unwrap();
+    }
+
+    #[test]
+    fn loads_secret_and_checks_password() {
+        let filename = keystore_file("keystore_scrypt.json", Kdf::Scrypt { log_n: 10, r: 8, p: 1 });
+        assert_eq!(load_secret_encrypted(&filename, "p").unwrap(), vec![7; 32]);
+        assert!(matches!(load_secret_encrypted(&filename, "q"), Err(WalletError::WrongPassword)));
+    }
+
+    #[test]
+    fn rejects_hostile_kdf_parameters() {
+        let filename = keystore_file("keystore_hostile.json", Kdf::Pbkdf2 { iterations: 1_000 });
+        for kdf in [
+            Kdf::Argon2 { memory_kib: u32::MAX, iterations: 2, parallelism: 1 },
+            Kdf::Argon2 { memory_kib: 19_456, iterations: 0, parallelism: 1 },
+            Kdf::Scrypt { log_n: 40, r: 8, p: 1 },
+            Kdf::Scrypt { log_n: 15, r: 0, p: 1 },
+            Kdf::Pbkdf2 { iterations: u32::MAX },
+            Kdf::Pbkdf2 { iterations: 0 },
+        ] {
+            tamper(&filename, kdf.clone());
+            assert!(matches!(load_secret_encrypted(&filename, "p"), Err(WalletError::InvalidFile(_))), "{:?}", kdf);
+        }
+    }
+}
@@ -0,0 +1,167 @@
+// ограничения нагрузки на API: частота запросов каждого клиента, размер тела запроса
+// и число одновременных тяжелых операций (майнинг блока, получение ключа из пароля).
+// клиент с ключом API считается по ключу, без ключа - по IP-адресу,
+// с IP-адреса списываются и запросы с неверным ключом.
+// отказы считаются, чтобы их было видно в метриках
+
+use crate::api::{reply, AppState};
+use crate::auth::ApiKeyId;
+use crate::i18n::{fail, Message};
+use axum::extract::{ConnectInfo, Request, State};
+use axum::http::header::{CONTENT_LENGTH, RETRY_AFTER};
+use axum::http::StatusCode;
+use axum::middleware::Next;
+use axum::response::Response;
+use std::collections::HashMap;
+use std::net::SocketAddr;
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::Mutex;
+use std::time::Instant;
+use tokio::sync::{Semaphore, SemaphorePermit};
+
+// наибольший размер тела запроса
+pub const MAX_BODY_SIZE: usize = 256 * 1024;
+
+pub struct Limits {
+    // запросов в секунду на клиента и сколько запросов можно сделать подряд
+    rate: f64,
+    burst: f64,
+    buckets: Mutex<HashMap<String, Bucket>>,
+    expensive: Semaphore,
+    pub rejected_rate: AtomicU64,
+    pub rejected_concurrency: AtomicU64,
+    pub rejected_body_size: AtomicU64,
+}
+
+// клиент, которому засчитан запрос; кладется в расширения запроса, чтобы тяжелые вызовы
+// внутри пакета JSON-RPC списывались с того же клиента
+#[derive(Clone)]
+pub struct Client(pub String);
+
+struct Bucket {
+    tokens: f64,
+    updated: Instant,
+}
+
+impl Limits {
+    pub fn new(rate: f64, burst: u32, max_expensive: usize) -> Self {
+        // при нулевой частоте опустевший запас не пополнялся бы никогда
+        if !(rate > 0.0 && rate.is_finite()) || burst == 0 {
+            fail(Message::InvalidRateLimit);
+        }
+        Limits {
+            rate,
+            burst: burst as f64,
+            buckets: Mutex::new(HashMap::new()),
+            expensive: Semaphore::new(max_expensive),
+            rejected_rate: AtomicU64::new(0),
+            rejected_concurrency: AtomicU64::new(0),
+            rejected_body_size: AtomicU64::new(0),
+        }
+    }
+
+    // None, если запрос можно выполнить, иначе через сколько секунд появится следующий
+    fn take(&self, client: String) -> Option<u64> {
+        let now = Instant::now();
+        let mut buckets = self.buckets.lock().unwrap();
+        let bucket = buckets.entry(client).or_insert(Bucket { tokens: self.burst, updated: now });
+        let elapsed = now.duration_since(bucket.updated).as_secs_f64();
+        bucket.tokens = (bucket.tokens + elapsed * self.rate).min(self.burst);
+        bucket.updated = now;
+
+        if bucket.tokens >= 1.0 {
+            bucket.tokens -= 1.0;
+            None
+        } else {
+            Some(((1.0 - bucket.tokens) / self.rate).ceil() as u64)
+        }
+    }
+
+    // списывает запрос с клиента; None, если запрос можно выполнить, иначе ответ 429
+    fn charge(&self, client: String) -> Option<Response> {
+        let retry_after = self.take(client)?;
+        self.rejected_rate.fetch_add(1, Ordering::Relaxed);
+        Some(too_many_requests(retry_after, Message::TooManyRequests))
+    }
+
+    // неудачная проверка ключа списывается с IP-адреса, иначе подбор ключей не ограничен частотой
+    pub fn charge_failed_auth(&self, peer: SocketAddr) -> Option<Response> {
+        self.charge(ip_client(peer))
+    }
+
+    fn acquire_expensive(&self) -> Option<SemaphorePermit<'_>> {
+        let permit = self.expensive.try_acquire().ok();
+        if permit.is_none() {
+            self.rejected_concurrency.fetch_add(1, Ordering::Relaxed);
+        }
+        permit
+    }
+
+    // тяжелая операция внутри уже принятого запроса расходует еще один запрос клиента и место
+    // среди одновременных операций; место держится, пока жив возвращенный permit
+    pub fn expensive_call(&self, client: &Client) -> Result<SemaphorePermit<'_>, Message> {
+        if self.take(client.0.clone()).is_some() {
+            self.rejected_rate.fetch_add(1, Ordering::Relaxed);
+            return Err(Message::TooManyRequests);
+        }
+        self.acquire_expensive().ok_or(Message::NodeBusy)
+    }
+
+    // клиенты, которые давно не обращались, снова получили бы полный запас
+    pub fn purge(&self) {
+        let now = Instant::now();
+        let full_after = self.burst / self.rate;
+        self.buckets
+            .lock()
+            .unwrap()
+            .retain(|_, bucket| now.duration_since(bucket.updated).as_secs_f64() < full_after);
+    }
+}
+
+fn ip_client(peer: SocketAddr) -> String {
+    format!("ip:{}", peer.ip())
+}
+
+fn too_many_requests(retry_after: u64, message: Message) -> Response {
+    let mut response = reply(StatusCode::TOO_MANY_REQUESTS, message);
+    response.headers_mut().insert(RETRY_AFTER, retry_after.into());
+    response
+}
+
+pub async fn rate_limit(
+    State(state): State<AppState>,
+    ConnectInfo(peer): ConnectInfo<SocketAddr>,
+    mut request: Request,
+    next: Next,
+) -> Response {
+    let limits = &state.limits;
+
+    // тело без Content-Length дочитывается с тем же пределом в обработчике
+    let content_length = request
+        .headers()
+        .get(CONTENT_LENGTH)
+        .and_then(|value| value.to_str().ok())
+        .and_then(|value| value.parse::<usize>().ok());
+    if content_length.is_some_and(|length| length > MAX_BODY_SIZE) {
+        limits.rejected_body_size.fetch_add(1, Ordering::Relaxed);
+        return reply(StatusCode::PAYLOAD_TOO_LARGE, Message::PayloadTooLarge);
+    }
+
+    let client = match request.extensions().get::<ApiKeyId>() {
+        Some(ApiKeyId(id)) => format!("key:{}", id),
+        None => ip_client(peer),
+    };
+    if let Some(response) = limits.charge(client.clone()) {
+        return response;
+    }
+    request.extensions_mut().insert(Client(client));
+    next.run(request).await
+}
+
+// тяжелые операции не ждут в очереди: при занятых местах запрос сразу отклоняется
+pub async fn limit_concurrency(State(state): State<AppState>, request: Request, next: Next) -> Response {
+    let Some(_permit) = state.limits.acquire_expensive() else {
+        return too_many_requests(1, Message::NodeBusy);
+    };
+    next.run(request).await
+}
@@ -1,54 +1,219 @@
+mod address;
+mod auth;
 mod block;
 mod blockchain;
+mod chain_spec;
+mod events;
+mod hd_wallet;
+mod i18n;
+mod keystore;
+mod limits;
+mod metrics;
 mod p2p;
+mod partial_tx;
+mod rpc;
 mod transaction;
+mod tx_cli;
 mod utils;
 mod wallet;
+mod wallet_cli;
+mod wallet_manager;
 mod api;
 
-use crate::api::AppState;
+use crate::api::{ApiConfig, AppState};
+use crate::auth::ApiAuth;
+use crate::i18n::{fail, Lang, Message};
+use crate::limits::Limits;
+use crate::metrics::Metrics;
 use crate::p2p::P2P;
 use blockchain::Blockchain;
-use clap::Parser;
+use chain_spec::ChainSpec;
+use clap::{Parser, Subcommand, ValueEnum};
+use std::io::IsTerminal;
+use std::net::{IpAddr, SocketAddr};
 use std::sync::{Arc, Mutex};
 use std::thread;
+use tracing_subscriber::EnvFilter;
+use tx_cli::TxCommand;
+use wallet_cli::WalletCommand;
+use wallet_manager::WalletManager;
 
 #[derive(Parser)]
 struct Cli {
-    #[arg(long)]
-    db: String,
-    #[arg(long)]
-    p2p_port: u16,
-    #[arg(long)]
-    api_port: u16,
-    #[arg(long)]
-    nodes: String,
+    #[command(subcommand)]
+    command: Command,
+    /// Фильтр журнала в формате RUST_LOG, например info,blockchain::p2p=debug
+    #[arg(long, global = true)]
+    log_filter: Option<String>,
+    /// Формат записей журнала
+    #[arg(long, global = true, value_enum, default_value_t)]
+    log_format: LogFormat,
+    /// Язык сообщений; API отвечает на языке из Accept-Language запроса, если он поддерживается
+    #[arg(long, global = true, value_enum, default_value_t)]
+    lang: Lang,
+}
+
+#[derive(Clone, Copy, Default, ValueEnum)]
+enum LogFormat {
+    #[default]
+    Text,
+    // одна запись - одна строка JSON с полями и цепочкой span
+    Json,
+}
+
+#[derive(Subcommand)]
+enum Command {
+    /// Запустить узел
+    Node {
+        #[arg(long)]
+        db: String,
+        #[arg(long)]
+        p2p_port: u16,
+        #[arg(long)]
+        api_port: u16,
+        /// Адрес, на котором узел принимает соединения других узлов
+        #[arg(long, default_value = "127.0.0.1")]
+        p2p_bind: IpAddr,
+        /// Адрес, на котором работает API
+        #[arg(long, default_value = "127.0.0.1")]
+        api_bind: IpAddr,
+        /// Сертификат в PEM для работы API по HTTPS
+        #[arg(long, requires = "tls_key")]
+        tls_cert: Option<String>,
+        /// Закрытый ключ сертификата в PEM
+        #[arg(long, requires = "tls_cert")]
+        tls_key: Option<String>,
+        /// Источник, которому браузер разрешит обращаться к API, или * для любого
+        #[arg(long = "cors-origin")]
+        cors_origins: Vec<String>,
+        /// Запросов в секунду от одного клиента API
+        #[arg(long, default_value_t = 10.0)]
+        rate_limit: f64,
+        /// Сколько запросов клиент может сделать подряд сверх частоты
+        #[arg(long, default_value_t = 20)]
+        rate_burst: u32,
+        /// Сколько тяжелых запросов (отправка транзакций, операции с паролями) выполняется одновременно
+        #[arg(long, default_value_t = 2)]
+        max_expensive_requests: usize,
+        #[arg(long)]
+        nodes: String,
+        #[arg(long)]
+        spec: Option<String>,
+        /// Каталог с кошельками узла
+        #[arg(long, default_value = "wallets")]
+        wallet_dir: String,
+        /// Файл с ключами доступа к API, без него API доступен только на чтение
+        #[arg(long)]
+        api_keys: Option<String>,
+    },
+    /// Подобрать genesis_nonce для спецификации цепочки
+    Genesis {
+        #[arg(long)]
+        spec: Option<String>,
+    },
+    /// Операции с файлами кошельков
+    #[command(subcommand)]
+    Wallet(WalletCommand),
+    /// Подпись транзакций вне узла
+    #[command(subcommand)]
+    Tx(TxCommand),
 }
 
 fn main() {
     let cli = Cli::parse();
+    init_logging(cli.log_filter, cli.log_format);
+    i18n::set_default_lang(cli.lang);
 
-    let db_name = cli.db;
-    let blockchain = Arc::new(Mutex::new(Blockchain::new(&db_name, 3)));
+    match cli.command {
+        Command::Node {
+            db,
+            p2p_port,
+            api_port,
+            p2p_bind,
+            api_bind,
+            tls_cert,
+            tls_key,
+            cors_origins,
+            rate_limit,
+            rate_burst,
+            max_expensive_requests,
+            nodes,
+            spec,
+            wallet_dir,
+            api_keys,
+        } => {
+            let api_config = ApiConfig {
+                addr: SocketAddr::new(api_bind, api_port),
+                tls: tls_cert.zip(tls_key),
+                cors_origins,
+                auth: Arc::new(ApiAuth::load_or_default(api_keys)),
+                limits: Arc::new(Limits::new(rate_limit, rate_burst, max_expensive_requests)),
+            };
+            let p2p_addr = SocketAddr::new(p2p_bind, p2p_port);
+            run_node(db, p2p_addr, nodes, ChainSpec::load_or_default(spec), wallet_dir, api_config);
+        }
+        Command::Genesis { spec } => {
+            let block = ChainSpec::load_or_default(spec).mine_genesis_block();
+            println!("{}", Message::GenesisNonce(block.value));
+            println!("{}", Message::GenesisHash(block.hash));
+        }
+        Command::Wallet(command) => wallet_cli::run(command),
+        Command::Tx(command) => tx_cli::run(command),
+    }
+}
+
+// журнал пишется в stderr, чтобы не смешиваться с выводом команд кошелька.
+// без --log-filter используется переменная RUST_LOG, а без нее уровень info
+fn init_logging(filter: Option<String>, format: LogFormat) {
+    let filter = match filter {
+        Some(filter) => EnvFilter::try_new(filter).unwrap_or_else(|e| fail(Message::InvalidLogFilter(e.to_string()))),
+        None => EnvFilter::try_from_default_env().unwrap_or_else(|_| EnvFilter::new("info")),
+    };
+    let subscriber = tracing_subscriber::fmt()
+        .with_env_filter(filter)
+        .with_writer(std::io::stderr)
+        .with_ansi(std::io::stderr().is_terminal());
+    match format {
+        LogFormat::Text => subscriber.init(),
+        LogFormat::Json => subscriber.json().with_current_span(true).with_span_list(true).init(),
+    }
+}
+
+fn run_node(
+    db_name: String,
+    p2p_addr: SocketAddr,
+    nodes: String,
+    spec: ChainSpec,
+    wallet_dir: String,
+    api_config: ApiConfig,
+) {
+    let wallets = Arc::new(WalletManager::new(&wallet_dir, &spec.address_prefix).unwrap_or_else(|e| fail(e)));
+    let blockchain = Arc::new(Mutex::new(Blockchain::new(&db_name, spec)));
 
-    let nodes = cli.nodes
+    let nodes = nodes
         .split(',')
         .filter(|s| !s.is_empty())
         .map(|s| s.to_string())
         .collect::<Vec<String>>();
 
-    let p2p = P2P::new(nodes, db_name);
-    let p2p_port = cli.p2p_port;
+    let metrics = Arc::new(Metrics::default());
+    let p2p = P2P::new(nodes, blockchain.clone(), metrics.clone());
 
     let p2p_server = p2p.clone();
     thread::spawn(move || {
-        let bind_addr = format!("127.0.0.1:{}", p2p_port);
-        p2p_server.start_server(bind_addr);
+        p2p_server.start_server(p2p_addr);
     });
 
     let p2p_api = p2p.clone();
-    let api_port = cli.api_port;
     tokio::runtime::Runtime::new().unwrap().block_on(async move {
-        api::start_api(AppState { blockchain, p2p: Arc::new(p2p_api)}, api_port).await;
+        let state = AppState {
+            blockchain,
+            p2p: Arc::new(p2p_api),
+            wallets,
+            auth: api_config.auth.clone(),
+            limits: api_config.limits.clone(),
+            metrics,
+        };
+        api::start_api(state, api_config).await;
     });
 }
@@ -0,0 +1,146 @@
+// метрики узла в текстовом формате Prometheus.
+// счетчики событий копятся здесь, состояние цепочки считывается в момент запроса /metrics
+
+use crate::api::{self, AppState};
+use crate::blockchain::BlockError;
+use axum::extract::{MatchedPath, Request, State};
+use axum::http::header::CONTENT_TYPE;
+use axum::middleware::Next;
+use axum::response::{IntoResponse, Response};
+use std::collections::BTreeMap;
+use std::fmt::Write;
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::Mutex;
+use std::time::Instant;
+
+// границы корзин гистограммы времени ответа API в секундах
+const LATENCY_BUCKETS: [f64; 11] = [0.005, 0.01, 0.025, 0.05, 0.1, 0.25, 0.5, 1.0, 2.5, 5.0, 10.0];
+// по скольким последним блокам оценивается хешрейт
+const HASHRATE_WINDOW: u64 = 10;
+
+#[derive(Default)]
+pub struct Metrics {
+    blocks_received: AtomicU64,
+    // причина отказа - вариант BlockError
+    blocks_rejected: Mutex<BTreeMap<String, u64>>,
+    // маршрут и метод запроса
+    latencies: Mutex<BTreeMap<(String, String), Histogram>>,
+}
+
+#[derive(Default)]
+struct Histogram {
+    buckets: [u64; LATENCY_BUCKETS.len()],
+    sum: f64,
+    count: u64,
+}
+
+impl Metrics {
+    pub fn block_received(&self) {
+        self.blocks_received.fetch_add(1, Ordering::Relaxed);
+    }
+
+    pub fn block_rejected(&self, e: &BlockError) {
+        *self.blocks_rejected.lock().unwrap().entry(format!("{:?}", e)).or_insert(0) += 1;
+    }
+
+    fn observe_latency(&self, route: String, method: String, seconds: f64) {
+        let mut latencies = self.latencies.lock().unwrap();
+        let histogram = latencies.entry((route, method)).or_default();
+        for (bucket, bound) in histogram.buckets.iter_mut().zip(LATENCY_BUCKETS) {
+            if seconds <= bound {
+                *bucket += 1;
+            }
+        }
+        histogram.sum += seconds;
+        histogram.count += 1;
+    }
+}
+
+// время ответа по маршрутам; подключается после маршрутизации, поэтому шаблон пути уже известен
+pub async fn track_latency(State(state): State<AppState>, request: Request, next: Next) -> Response {
+    let route = request
+        .extensions()
+        .get::<MatchedPath>()
+        .map(|path| path.as_str().to_string())
+        .unwrap_or_default();
+    let method = request.method().to_string();
+    let started = Instant::now();
+    let response = next.run(request).await;
+    state.metrics.observe_latency(route, method, started.elapsed().as_secs_f64());
+    response
+}
+
+fn header(out: &mut String, name: &str, kind: &str, help: &str) {
+    writeln!(out, "# HELP {} {}", name, help).unwrap();
+    writeln!(out, "# TYPE {} {}", name, kind).unwrap();
+}
+
+fn single(out: &mut String, name: &str, kind: &str, help: &str, value: impl std::fmt::Display) {
+    header(out, name, kind, help);
+    writeln!(out, "{} {}", name, value).unwrap();
+}
+
+pub async fn render(State(state): State<AppState>) -> impl IntoResponse {
+    let mut out = String::new();
+
+    {
+        let blockchain = state.blockchain.lock().unwrap();
+        let tip = blockchain.latest_block().unwrap();
+        let difficulty = blockchain.spec().difficulty;
+
+        // ожидаемое число хешей на блок - 16 в степени числа нулевых hex-цифр в начале хеша.
+        // время первого блока задано спецификацией, поэтому он в оценку не входит
+        let window = tip.index.saturating_sub(1).min(HASHRATE_WINDOW);
+        let first = blockchain.load_block(tip.index - window).unwrap();
+        let hashrate = if window > 0 && tip.timestamp > first.timestamp {
+            let interval = (tip.timestamp - first.timestamp) as f64 / 1000.0 / window as f64;
+            16f64.powi(difficulty as i32) / interval
+        } else {
+            0.0
+        };
+
+        single(&mut out, "blockchain_height", "gauge", "Высота вершины цепочки", tip.index);
+        single(&mut out, "blockchain_difficulty", "gauge", "Число нулевых hex-цифр в начале хеша блока", difficulty);
+        single(&mut out, "blockchain_hashrate", "gauge", "Оценка хешрейта сети по последним блокам, хешей в секунду", hashrate);
+        single(&mut out, "blockchain_db_size_bytes", "gauge", "Размер базы sled на диске", blockchain.db_size());
+    }
+
+    single(&mut out, "blockchain_peers", "gauge", "Известные узлы", api::peers(&state).len());
+
+    let metrics = &state.metrics;
+    single(
+        &mut out,
+        "blockchain_blocks_received_total",
+        "counter",
+        "Блоки от других узлов, добавленные в цепочку",
+        metrics.blocks_received.load(Ordering::Relaxed),
+    );
+    header(&mut out, "blockchain_blocks_rejected_total", "counter", "Отклоненные блоки от других узлов по причинам");
+    for (reason, count) in metrics.blocks_rejected.lock().unwrap().iter() {
+        writeln!(out, "blockchain_blocks_rejected_total{{reason=\"{}\"}} {}", reason, count).unwrap();
+    }
+
+    let limits = &state.limits;
+    header(&mut out, "api_rejected_requests_total", "counter", "Запросы к API, отклоненные ограничениями нагрузки");
+    for (reason, count) in [
+        ("rate", &limits.rejected_rate),
+        ("concurrency", &limits.rejected_concurrency),
+        ("body_size", &limits.rejected_body_size),
+    ] {
+        writeln!(out, "api_rejected_requests_total{{reason=\"{}\"}} {}", reason, count.load(Ordering::Relaxed)).unwrap();
+    }
+
+    let name = "api_request_duration_seconds";
+    header(&mut out, name, "histogram", "Время ответа API по маршрутам");
+    for ((route, method), histogram) in metrics.latencies.lock().unwrap().iter() {
+        let labels = format!("route=\"{}\",method=\"{}\"", route, method);
+        for (bound, bucket) in LATENCY_BUCKETS.iter().zip(histogram.buckets) {
+            writeln!(out, "{}_bucket{{{},le=\"{}\"}} {}", name, labels, bound, bucket).unwrap();
+        }
+        writeln!(out, "{}_bucket{{{},le=\"+Inf\"}} {}", name, labels, histogram.count).unwrap();
+        writeln!(out, "{}_sum{{{}}} {}", name, labels, histogram.sum).unwrap();
+        writeln!(out, "{}_count{{{}}} {}", name, labels, histogram.count).unwrap();
+    }
+
+    ([(CONTENT_TYPE, "text/plain; version=0.0.4")], out)
+}
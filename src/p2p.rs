@@ -3,16 +3,24 @@
 
 // связь между узлами - tcp
 // команды: ping, tx, block
+// сообщения от узлов с другой сетью или другим первым блоком отбрасываются; первый блок
+// зависит от всей спецификации, поэтому узлы с разными правилами консенсуса не соединяются
 
 use serde::{Deserialize, Serialize};
 use std::io::{Read, Write};
-use std::net::{TcpListener, TcpStream};
+use std::net::{SocketAddr, TcpListener, TcpStream};
+use std::sync::{Arc, Mutex};
 use std::thread;
 use crate::block::Block;
 use crate::blockchain::Blockchain;
+use crate::i18n::{self, fail};
+use crate::metrics::Metrics;
+use tracing::{debug, info, info_span, warn};
 
 #[derive(Debug, Serialize, Deserialize)]
 pub struct Message {
+    pub network_id: String,
+    pub genesis_hash: String,
     pub command: String,
     pub payload: Vec<u8>,
 }
@@ -20,36 +28,54 @@ pub struct Message {
 #[derive(Clone)]
 pub struct P2P {
     pub nodes: Vec<String>,
-    pub db: String,
+    pub blockchain: Arc<Mutex<Blockchain>>,
+    metrics: Arc<Metrics>,
+    network_id: String,
+    genesis_hash: String,
 }
 
 impl P2P {
-    pub fn new(nodes: Vec<String>, db: String) -> Self {
-        Self { nodes, db }
+    pub fn new(nodes: Vec<String>, blockchain: Arc<Mutex<Blockchain>>, metrics: Arc<Metrics>) -> Self {
+        let (network_id, genesis_hash) = {
+            let chain = blockchain.lock().unwrap();
+            (chain.spec().network_id.clone(), chain.genesis_hash())
+        };
+        Self { nodes, blockchain, metrics, network_id, genesis_hash }
     }
 
-    pub fn start_server(&self, addr: String) {
-        let listener = TcpListener::bind(addr).expect("Не удалось запустить сервер");
+    pub fn message(&self, command: &str, payload: Vec<u8>) -> Message {
+        Message {
+            network_id: self.network_id.clone(),
+            genesis_hash: self.genesis_hash.clone(),
+            command: command.to_string(),
+            payload,
+        }
+    }
 
-        println!("Сервер успешно запущен");
+    pub fn start_server(&self, addr: SocketAddr) {
+        let listener = TcpListener::bind(addr)
+            .unwrap_or_else(|e| fail(i18n::Message::P2pBindFailed(addr.to_string(), e.to_string())));
+
+        info!(%addr, "P2P-сервер запущен");
 
         for stream in listener.incoming() {
             match stream {
                 Ok(stream) => {
-                    println!("Новое соединение: {}", stream.peer_addr().unwrap());
-                    let db_path = self.db.clone();
+                    let p2p = self.clone();
                     thread::spawn(move || {
-                        P2P::handle_connection(stream, db_path);
+                        let _span = info_span!("connection", peer = %stream.peer_addr().unwrap()).entered();
+                        debug!("новое соединение");
+                        p2p.handle_connection(stream);
                     });
                 }
                 Err(e) => {
-                    println!("{}", e)
+                    warn!(error = %e, "не удалось принять соединение")
                 }
             }
         }
     }
 
-    fn handle_connection(mut stream: TcpStream, db: String) {
+    fn handle_connection(&self, mut stream: TcpStream) {
         let mut buffer = Vec::new();
         stream.read_to_end(&mut buffer).unwrap();
 
@@ -59,28 +85,38 @@ impl P2P {
 
         let msg: Message = bincode::deserialize(&buffer).unwrap();
 
-        println!("Получено сообщение {:?}", msg);
+        debug!(command = %msg.command, size = msg.payload.len(), "получено сообщение");
+
+        if msg.network_id != self.network_id || msg.genesis_hash != self.genesis_hash {
+            warn!(network_id = %msg.network_id, genesis_hash = %msg.genesis_hash, "сообщение от узла другой сети отклонено");
+            return;
+        }
 
         match msg.command.as_str() {
             "ping" => {
-                print!("Ping от другого узла");
+                debug!("ping от другого узла");
             }
             "tx" => {
-                println!("Получена транзакция");
+                debug!("получена транзакция");
             }
             "block" => {
-                println!("Получен блок");
                 let block: Block = bincode::deserialize(&msg.payload).unwrap();
-
-                let mut blockchain = Blockchain::new(&db, 3);
-                if blockchain.add_block_from_p2p(block) {
-                    println!("Блок успешно добавлен в локальный блокчейн {}", db)
-                } else {
-                    println!("Блок невалидный");
+                let _span = info_span!("block", height = block.index, hash = %block.hash).entered();
+
+                let mut blockchain = self.blockchain.lock().unwrap();
+                match blockchain.add_block_from_p2p(block) {
+                    Ok(()) => {
+                        self.metrics.block_received();
+                        info!("блок от другого узла добавлен в цепочку");
+                    }
+                    Err(e) => {
+                        self.metrics.block_rejected(&e);
+                        warn!(error = %e, "блок от другого узла отклонен");
+                    }
                 }
             }
-            _ => {
-                println!("Неизвестная команда")
+            command => {
+                warn!(command, "неизвестная команда")
             }
         }
     }
@@ -89,9 +125,9 @@ impl P2P {
         if let Ok(mut stream) = TcpStream::connect(node) {
             let data = bincode::serialize(message).unwrap();
             stream.write_all(&data).unwrap();
-            println!("Сообщение отправлено -> {}", node);
+            debug!(node, command = %message.command, "сообщение отправлено");
         } else {
-            println!("Не удалось подключиться к {}", node)
+            warn!(node, "не удалось подключиться к узлу")
         }
     }
 
@@ -0,0 +1,163 @@
+// переносимый формат неподписанной или частично подписанной транзакции.
+// онлайн-узел собирает транзакцию по состоянию цепочки (номер, комиссия, входы),
+// машина без сети проверяет ее по полям файла и подписывает, подписанный файл
+// отправляется обратно в узел
+
+use crate::chain_spec::ChainSpec;
+use crate::i18n::{self, Localize, Message};
+use crate::transaction::{Transaction, TxOutput};
+use crate::wallet::Wallet;
+use serde::{Deserialize, Serialize};
+use std::fmt::{Display, Formatter};
+
+pub const PARTIAL_TX_VERSION: u32 = 1;
+
+#[derive(Clone, Serialize, Deserialize)]
+pub struct PartialTransaction {
+    pub version: u32,
+    // цепочка, для которой собрана транзакция; узел другой цепочки ее не примет
+    pub network_id: String,
+    pub genesis_hash: String,
+    // нужен подписывающему, чтобы сверить адрес своего ключа с отправителем
+    pub address_prefix: String,
+    pub transaction: Transaction,
+    // выходы, которые тратит транзакция в режиме UTXO, чтобы без сети проверить сумму входов
+    pub spent_outputs: Vec<TxOutput>,
+}
+
+#[derive(Debug)]
+pub enum PartialTxError {
+    InvalidFormat(String),
+    UnsupportedVersion(u32),
+    WrongNetwork,
+    WrongSigner,
+    KeyNotInMultisig,
+    Mismatch,
+    Incomplete,
+}
+
+impl Display for PartialTxError {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{}", self.text(i18n::lang()))
+    }
+}
+
+impl PartialTransaction {
+    pub fn new(spec: &ChainSpec, genesis_hash: &str, transaction: Transaction, spent_outputs: Vec<TxOutput>) -> Self {
+        PartialTransaction {
+            version: PARTIAL_TX_VERSION,
+            network_id: spec.network_id.clone(),
+            genesis_hash: genesis_hash.to_string(),
+            address_prefix: spec.address_prefix.clone(),
+            transaction,
+            spent_outputs,
+        }
+    }
+
+    pub fn from_json(json: &str) -> Result<Self, PartialTxError> {
+        let partial: PartialTransaction =
+            serde_json::from_str(json).map_err(|e| PartialTxError::InvalidFormat(e.to_string()))?;
+        partial.check_version()?;
+        Ok(partial)
+    }
+
+    pub fn to_json(&self) -> String {
+        serde_json::to_string_pretty(self).unwrap()
+    }
+
+    pub fn check_version(&self) -> Result<(), PartialTxError> {
+        if self.version != PARTIAL_TX_VERSION {
+            return Err(PartialTxError::UnsupportedVersion(self.version));
+        }
+        Ok(())
+    }
+
+    pub fn check_network(&self, network_id: &str, genesis_hash: &str) -> Result<(), PartialTxError> {
+        if self.network_id != network_id || self.genesis_hash != genesis_hash {
+            return Err(PartialTxError::WrongNetwork);
+        }
+        Ok(())
+    }
+
+    pub fn sign(&mut self, wallet: &Wallet) -> Result<(), PartialTxError> {
+        let data = self.transaction.signing_data();
+        let scheme = self.transaction.signature_type;
+        match self.transaction.multisig.as_mut() {
+            Some(multisig) => {
+                if !multisig.sign(wallet, data.as_bytes(), scheme) {
+                    return Err(PartialTxError::KeyNotInMultisig);
+                }
+            }
+            None => {
+                if wallet.address(&self.address_prefix) != self.transaction.from {
+                    return Err(PartialTxError::WrongSigner);
+                }
+                self.transaction.public_key = wallet.public_key.to_sec1_bytes().to_vec();
+                self.transaction.signature = wallet.sign(data.as_bytes(), scheme);
+            }
+        }
+        Ok(())
+    }
+
+    // добавляет подписи из другого файла той же транзакции
+    pub fn combine(&mut self, other: &PartialTransaction) -> Result<(), PartialTxError> {
+        if self.network_id != other.network_id
+            || self.genesis_hash != other.genesis_hash
+            || self.transaction.signing_data() != other.transaction.signing_data()
+        {
+            return Err(PartialTxError::Mismatch);
+        }
+
+        match (self.transaction.multisig.as_mut(), other.transaction.multisig.as_ref()) {
+            (Some(multisig), Some(other_multisig)) => {
+                if !multisig.merge(other_multisig) {
+                    return Err(PartialTxError::Mismatch);
+                }
+            }
+            (None, None) => {
+                if self.transaction.signature.is_empty() {
+                    self.transaction.public_key.clone_from(&other.transaction.public_key);
+                    self.transaction.signature.clone_from(&other.transaction.signature);
+                }
+            }
+            _ => return Err(PartialTxError::Mismatch),
+        }
+        Ok(())
+    }
+
+    pub fn is_complete(&self) -> bool {
+        match &self.transaction.multisig {
+            Some(multisig) => multisig.signature_count() >= multisig.threshold as usize,
+            None => !self.transaction.signature.is_empty(),
+        }
+    }
+}
+
+// сводка для проверки транзакции перед подписью
+impl Display for PartialTransaction {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        let tx = &self.transaction;
+        writeln!(f, "{}", Message::TxChain(self.network_id.clone(), self.genesis_hash.clone()))?;
+        writeln!(f, "{}", Message::TxFrom(tx.from.clone()))?;
+        writeln!(f, "{}", Message::TxTo(tx.to.clone()))?;
+        writeln!(f, "{}", Message::TxAmount(tx.amount))?;
+        writeln!(f, "{}", Message::TxFee(tx.fee))?;
+        writeln!(f, "{}", Message::TxSignatureScheme(format!("{:?}", tx.signature_type)))?;
+        if tx.inputs.is_empty() {
+            writeln!(f, "{}", Message::TxNonce(tx.nonce))?;
+        } else {
+            let inputs_total = self.spent_outputs.iter().map(|output| output.amount).sum::<f64>();
+            writeln!(f, "{}", Message::TxInputs(tx.inputs.len(), inputs_total))?;
+            for output in &tx.outputs {
+                writeln!(f, "{}", Message::TxOutput(output.amount, output.address.clone()))?;
+            }
+        }
+        let summary = match &tx.multisig {
+            Some(multisig) => {
+                Message::TxMultisigSignatures(multisig.signature_count(), multisig.public_keys.len(), multisig.threshold)
+            }
+            None => Message::TxSigned(!tx.signature.is_empty()),
+        };
+        write!(f, "{}", summary)
+    }
+}
@@ -0,0 +1,188 @@
+// JSON-RPC 2.0 поверх тех же операций, что и REST.
+// запрос может быть одиночным или пакетом; на уведомления (без id) узел не отвечает.
+// метода get_mempool нет: транзакция сразу включается в новый блок, и пула неподтвержденных
+// транзакций у узла нет
+
+use crate::api::{self, ApiError, AppState, BlockId};
+use crate::auth::Role;
+use crate::i18n::{self, Localize, Message};
+use crate::limits::Client;
+use crate::transaction::Transaction;
+use axum::extract::State;
+use axum::Extension;
+use axum::http::StatusCode;
+use axum::response::{IntoResponse, Response};
+use axum::Json;
+use serde::de::DeserializeOwned;
+use serde::{Deserialize, Serialize};
+use serde_json::{Map, Value};
+
+// стандартные коды ошибок JSON-RPC 2.0
+const PARSE_ERROR: i64 = -32700;
+const INVALID_REQUEST: i64 = -32600;
+const METHOD_NOT_FOUND: i64 = -32601;
+const INVALID_PARAMS: i64 = -32602;
+// коды узла из диапазона, отведенного под ошибки сервера
+const REJECTED: i64 = -32000;
+const NOT_FOUND: i64 = -32001;
+const FORBIDDEN: i64 = -32002;
+const LIMITED: i64 = -32003;
+
+// пакет выполняется в одном HTTP-запросе, поэтому его размер ограничен
+const MAX_BATCH_SIZE: usize = 100;
+
+#[derive(Deserialize)]
+struct Request {
+    jsonrpc: String,
+    method: String,
+    #[serde(default)]
+    params: Value,
+}
+
+#[derive(Serialize)]
+struct Error {
+    code: i64,
+    message: String,
+    data: ErrorData,
+}
+
+// код сообщения, как в ответах REST
+#[derive(Serialize)]
+struct ErrorData {
+    code: &'static str,
+}
+
+#[derive(Deserialize)]
+struct AddressParams {
+    address: String,
+}
+
+#[derive(Deserialize)]
+struct BlockParams {
+    block: BlockId,
+}
+
+#[derive(Deserialize)]
+struct TransactionParams {
+    transaction: Transaction,
+}
+
+impl From<ApiError> for Error {
+    fn from(e: ApiError) -> Self {
+        match e {
+            ApiError::InvalidParams(message) => error(INVALID_PARAMS, message),
+            ApiError::NotFound(message) => error(NOT_FOUND, message),
+            ApiError::Rejected(message) => error(REJECTED, message),
+        }
+    }
+}
+
+pub async fn handle(
+    State(state): State<AppState>,
+    Extension(role): Extension<Role>,
+    Extension(client): Extension<Client>,
+    body: String,
+) -> Response {
+    let request = match serde_json::from_str::<Value>(&body) {
+        Ok(request) => request,
+        Err(e) => return Json(response(Value::Null, Err(error(PARSE_ERROR, Message::InvalidJson(e.to_string()))))).into_response(),
+    };
+
+    let responses = match request {
+        Value::Array(batch) if batch.is_empty() => {
+            let e = error(INVALID_REQUEST, Message::EmptyBatch);
+            return Json(response(Value::Null, Err(e))).into_response();
+        }
+        Value::Array(batch) if batch.len() > MAX_BATCH_SIZE => {
+            let e = error(INVALID_REQUEST, Message::BatchTooLarge(MAX_BATCH_SIZE));
+            return Json(response(Value::Null, Err(e))).into_response();
+        }
+        Value::Array(batch) => {
+            let responses: Vec<Value> =
+                batch.into_iter().filter_map(|request| call(&state, role, &client, request)).collect();
+            (!responses.is_empty()).then_some(Value::Array(responses))
+        }
+        request => call(&state, role, &client, request),
+    };
+
+    match responses {
+        Some(responses) => Json(responses).into_response(),
+        None => StatusCode::NO_CONTENT.into_response(),
+    }
+}
+
+// ответ на один запрос пакета, None для уведомления
+fn call(state: &AppState, role: Role, client: &Client, request: Value) -> Option<Value> {
+    let id = request.get("id").cloned();
+    let request = match serde_json::from_value::<Request>(request) {
+        Ok(request) if request.jsonrpc == "2.0" => request,
+        _ => {
+            let e = error(INVALID_REQUEST, Message::InvalidRpcRequest);
+            return Some(response(id.unwrap_or(Value::Null), Err(e)));
+        }
+    };
+
+    let result = if role >= required_role(&request.method) {
+        dispatch(state, client, &request.method, request.params)
+    } else {
+        Err(error(FORBIDDEN, Message::Forbidden))
+    };
+    id.map(|id| response(id, result))
+}
+
+// методы требуют тех же ролей, что и соответствующие маршруты REST
+fn required_role(method: &str) -> Role {
+    match method {
+        "send_raw_transaction" => Role::Wallet,
+        _ => Role::Read,
+    }
+}
+
+fn dispatch(state: &AppState, client: &Client, method: &str, params: Value) -> Result<Value, Error> {
+    match method {
+        "get_balance" => {
+            let params: AddressParams = parse_params(params, &["address"])?;
+            to_value(api::balance(state, &params.address)?)
+        }
+        "get_block" => {
+            let params: BlockParams = parse_params(params, &["block"])?;
+            to_value(api::block(state, params.block)?)
+        }
+        "send_raw_transaction" => {
+            let params: TransactionParams = parse_params(params, &["transaction"])?;
+            // каждая отправка добывает блок, поэтому ограничивается так же, как POST /tx/send
+            let _permit = state.limits.expensive_call(client).map_err(|e| error(LIMITED, e))?;
+            to_value(api::send_transaction(state, params.transaction)?)
+        }
+        "get_peers" => to_value(api::peers(state)),
+        "get_chain_info" => to_value(api::chain_info(state)),
+        _ => Err(error(METHOD_NOT_FOUND, Message::UnknownMethod(method.to_string()))),
+    }
+}
+
+// параметры передаются объектом или массивом в порядке names
+fn parse_params<T: DeserializeOwned>(params: Value, names: &[&str]) -> Result<T, Error> {
+    let params = match params {
+        Value::Array(values) if values.len() <= names.len() => {
+            Value::Object(names.iter().map(|name| name.to_string()).zip(values).collect::<Map<_, _>>())
+        }
+        Value::Null => Value::Object(Map::new()),
+        params => params,
+    };
+    serde_json::from_value(params).map_err(|e| error(INVALID_PARAMS, Message::InvalidParams(e.to_string())))
+}
+
+fn to_value(value: impl Serialize) -> Result<Value, Error> {
+    Ok(serde_json::to_value(value).unwrap())
+}
+
+fn error(code: i64, message: impl Localize) -> Error {
+    Error { code, message: message.text(i18n::lang()), data: ErrorData { code: message.code() } }
+}
+
+fn response(id: Value, result: Result<Value, Error>) -> Value {
+    match result {
+        Ok(result) => serde_json::json!({ "jsonrpc": "2.0", "result": result, "id": id }),
+        Err(e) => serde_json::json!({ "jsonrpc": "2.0", "error": e, "id": id }),
+    }
+}
@@ -1,18 +1,153 @@
+use std::collections::HashSet;
 use std::fmt::{Display, Formatter};
+use k256::ecdsa::VerifyingKey;
 use serde::{Deserialize, Serialize};
-use crate::blockchain::TRANSACTION_FEE;
+use crate::address;
+use crate::utils::calculate_hash;
+use crate::wallet::{SignatureScheme, Wallet};
+
+pub const MAX_MULTISIG_KEYS: usize = 15;
+
+// ссылка на выход предыдущей транзакции (используется в режиме UTXO)
+#[derive(Clone, Debug, PartialEq, Eq, Hash, Serialize, Deserialize)]
+pub struct OutPoint {
+    pub txid: String,
+    pub vout: u32,
+}
+
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+pub struct TxOutput {
+    pub address: String,
+    pub amount: f64,
+}
+
+// подписи для траты с мультиподписного адреса M из N
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct Multisig {
+    pub threshold: u8,
+    // сжатые открытые ключи в порядке, из которого получен адрес
+    pub public_keys: Vec<Vec<u8>>,
+    // подпись на месте каждого ключа, None - владелец ключа еще не подписал
+    pub signatures: Vec<Option<Vec<u8>>>,
+}
 
 #[derive(Clone, Debug, Serialize, Deserialize)]
 pub struct Transaction {
     pub from: String,
     pub to: String,
     pub amount: f64,
+    pub fee: f64,
+    // порядковый номер транзакции отправителя в режиме учета по счетам, защищает от повтора
+    pub nonce: u64,
+    pub inputs: Vec<OutPoint>,
+    pub outputs: Vec<TxOutput>,
     pub signature: Vec<u8>,
-    pub public_key: Vec<u8>
+    pub public_key: Vec<u8>,
+    // схема всех подписей транзакции, в том числе подписей мультиподписи
+    pub signature_type: SignatureScheme,
+    // заполняется вместо signature и public_key, если отправитель - мультиподписной адрес
+    pub multisig: Option<Multisig>,
+}
+
+impl Transaction {
+    // высота блока в поле подписи делает id coinbase-транзакций уникальными
+    // (иначе одинаковые награды одному майнеру давали бы одинаковые выходы)
+    pub fn coinbase(network_address: &str, to: &str, amount: f64, height: u64) -> Self {
+        Transaction {
+            from: network_address.to_string(),
+            to: to.to_string(),
+            amount,
+            fee: 0.0,
+            nonce: 0,
+            inputs: vec![],
+            outputs: vec![TxOutput { address: to.to_string(), amount }],
+            signature: height.to_be_bytes().to_vec(),
+            public_key: vec![],
+            signature_type: SignatureScheme::Ecdsa,
+            multisig: None,
+        }
+    }
+
+    pub fn id(&self) -> String {
+        calculate_hash(format!("{:?}", self))
+    }
+
+    pub fn signing_data(&self) -> String {
+        format!(
+            "{}{}{}{}{}{:?}{:?}{:?}",
+            self.from, self.to, self.amount, self.fee, self.nonce, self.inputs, self.outputs, self.signature_type
+        )
+    }
+
+    pub fn is_coinbase(&self, network_address: &str) -> bool {
+        self.from == network_address
+    }
+}
+
+impl Multisig {
+    pub fn new(threshold: u8, public_keys: Vec<Vec<u8>>) -> Self {
+        let signatures = vec![None; public_keys.len()];
+        Multisig { threshold, public_keys, signatures }
+    }
+
+    pub fn address(&self, prefix: &str) -> String {
+        address::from_multisig(self.threshold, &self.public_keys, prefix)
+    }
+
+    // от 1 до MAX_MULTISIG_KEYS разных сжатых ключей и порог не больше их числа
+    pub fn is_well_formed(&self) -> bool {
+        let keys = self.public_keys.len();
+        let unique_keys = self.public_keys.iter().collect::<HashSet<_>>().len();
+        (1..=MAX_MULTISIG_KEYS).contains(&keys)
+            && unique_keys == keys
+            && (1..=keys).contains(&(self.threshold as usize))
+            && self.signatures.len() == keys
+            && self.public_keys.iter().all(|key| {
+                key.len() == 33 && VerifyingKey::from_sec1_bytes(key).is_ok()
+            })
+    }
+
+    pub fn signature_count(&self) -> usize {
+        self.signatures.iter().flatten().count()
+    }
+
+    // пары ключ - подпись для проверки; подписей должно быть не меньше порога
+    pub fn signed_keys(&self) -> impl Iterator<Item = (&[u8], &[u8])> {
+        self.public_keys
+            .iter()
+            .zip(&self.signatures)
+            .filter_map(|(key, signature)| Some((key.as_slice(), signature.as_deref()?)))
+    }
+
+    // false, если ключа кошелька нет среди ключей адреса
+    pub fn sign(&mut self, wallet: &Wallet, data: &[u8], scheme: SignatureScheme) -> bool {
+        let public_key = wallet.public_key.to_sec1_bytes().to_vec();
+        let Some(slot) = self.public_keys.iter().position(|key| *key == public_key) else {
+            return false;
+        };
+        self.signatures[slot] = Some(wallet.sign(data, scheme));
+        true
+    }
+
+    // дополняет подписи подписями другого участника той же мультиподписи
+    pub fn merge(&mut self, other: &Multisig) -> bool {
+        if self.threshold != other.threshold
+            || self.public_keys != other.public_keys
+            || self.signatures.len() != other.signatures.len()
+        {
+            return false;
+        }
+        for (signature, other_signature) in self.signatures.iter_mut().zip(&other.signatures) {
+            if signature.is_none() {
+                signature.clone_from(other_signature);
+            }
+        }
+        true
+    }
 }
 
 impl Display for Transaction {
     fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
-        write!(f, "{} -> {}: {} (комиссия {})", self.from, self.to, self.amount, TRANSACTION_FEE)
+        write!(f, "{} -> {}: {} (fee {})", self.from, self.to, self.amount, self.fee)
     }
-}
\ No newline at end of file
+}
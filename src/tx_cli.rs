@@ -0,0 +1,152 @@
+// подпись транзакций вне узла: create и send обращаются к API онлайн-узла,
+// inspect, sign и combine работают только с файлами и не требуют сети
+
+use crate::api::ApiMessage;
+use crate::i18n::{self, fail, Message};
+use crate::partial_tx::{PartialTransaction, PartialTxError};
+use crate::wallet::{SignatureScheme, StoredWallet};
+use crate::wallet_cli::read_line;
+use crate::wallet_manager::WalletError;
+use clap::Subcommand;
+use serde::Serialize;
+use std::fs::{read_to_string, write};
+
+#[derive(Subcommand)]
+pub enum TxCommand {
+    /// Собрать неподписанную транзакцию на онлайн-узле
+    Create {
+        /// Адрес API узла, например http://127.0.0.1:8001
+        #[arg(long)]
+        node: String,
+        /// Ключ доступа к API узла с ролью wallet
+        #[arg(long)]
+        token: Option<String>,
+        #[arg(long)]
+        from: String,
+        #[arg(long)]
+        to: String,
+        #[arg(long)]
+        amount: f64,
+        #[arg(long, value_enum, default_value_t)]
+        signature_type: SignatureScheme,
+        #[arg(long)]
+        out: String,
+    },
+    /// Показать содержимое файла транзакции
+    Inspect {
+        #[arg(long)]
+        tx: String,
+    },
+    /// Подписать транзакцию ключом из файла кошелька
+    Sign {
+        #[arg(long)]
+        tx: String,
+        #[arg(long)]
+        wallet: String,
+        /// Номер адреса в HD-кошельке
+        #[arg(long, default_value_t = 0)]
+        account: u32,
+        /// Куда записать результат, по умолчанию файл транзакции перезаписывается
+        #[arg(long)]
+        out: Option<String>,
+    },
+    /// Объединить подписи участников мультиподписи
+    Combine {
+        #[arg(long = "tx", required = true)]
+        txs: Vec<String>,
+        #[arg(long)]
+        out: String,
+    },
+    /// Отправить подписанную транзакцию в узел
+    Send {
+        #[arg(long)]
+        node: String,
+        #[arg(long)]
+        token: Option<String>,
+        #[arg(long)]
+        tx: String,
+    },
+}
+
+#[derive(Serialize)]
+struct CreateRequest<'a> {
+    from: &'a str,
+    to: &'a str,
+    amount: f64,
+    signature_type: SignatureScheme,
+}
+
+pub fn run(command: TxCommand) {
+    match command {
+        TxCommand::Create { node, token, from, to, amount, signature_type, out } => {
+            let request = CreateRequest { from: &from, to: &to, amount, signature_type };
+            let response = post(&node, token.as_deref(), "/psbt/create", &request);
+            let partial = PartialTransaction::from_json(&response).unwrap_or_else(|e| fail(e));
+            save(&out, &partial);
+            println!("{}", partial);
+        }
+        TxCommand::Inspect { tx } => {
+            let partial = load(&tx);
+            println!("{}", partial);
+            println!("{}", Message::ReadyToSend(partial.is_complete()));
+        }
+        TxCommand::Sign { tx, wallet, account, out } => {
+            let mut partial = load(&tx);
+            println!("{}", partial);
+            let password = read_line(Message::PromptWalletPassword);
+            let stored = StoredWallet::load(&wallet, &password).unwrap_or_else(|e| fail(e));
+            let Some(signer) = stored.account(account) else {
+                fail(WalletError::UnknownAccount(account));
+            };
+            partial.sign(&signer).unwrap_or_else(|e| fail(e));
+            save(out.as_deref().unwrap_or(&tx), &partial);
+            println!("{}", Message::TransactionSigned);
+        }
+        TxCommand::Combine { txs, out } => {
+            let mut partials = txs.iter().map(|tx| load(tx));
+            let mut combined = partials.next().unwrap();
+            for partial in partials {
+                combined.combine(&partial).unwrap_or_else(|e| fail(e));
+            }
+            save(&out, &combined);
+            println!("{}", combined);
+        }
+        TxCommand::Send { node, token, tx } => {
+            let partial = load(&tx);
+            if !partial.is_complete() {
+                fail(PartialTxError::Incomplete);
+            }
+            let response = post(&node, token.as_deref(), "/psbt/send", &partial);
+            println!("{}", serde_json::from_str::<ApiMessage>(&response).map(|reply| reply.message).unwrap_or(response));
+        }
+    }
+}
+
+fn load(filename: &str) -> PartialTransaction {
+    let json = read_to_string(filename).unwrap_or_else(|e| fail(Message::OpenFailed(filename.to_string(), e.to_string())));
+    PartialTransaction::from_json(&json).unwrap_or_else(|e| fail(e))
+}
+
+fn save(filename: &str, partial: &PartialTransaction) {
+    write(filename, partial.to_json()).unwrap_or_else(|e| fail(Message::WriteFailed(filename.to_string(), e.to_string())));
+}
+
+// ответ узла - JSON; при ошибке узел присылает код и текст причины на языке команды
+fn post(node: &str, token: Option<&str>, path: &str, body: &impl Serialize) -> String {
+    let url = format!("{}{}", node.trim_end_matches('/'), path);
+    let mut request = ureq::post(&url).set("Accept-Language", i18n::lang().tag());
+    if let Some(token) = token {
+        request = request.set("Authorization", &format!("Bearer {}", token));
+    }
+    match request.send_json(body) {
+        Ok(response) => response.into_string().unwrap(),
+        Err(ureq::Error::Status(_, response)) => {
+            let body = response.into_string().unwrap_or_default();
+            match serde_json::from_str::<ApiMessage>(&body) {
+                Ok(reply) => fail(reply.message),
+                Err(_) => fail(Message::NodeUnavailable(body)),
+            }
+        }
+        Err(e) => fail(Message::NodeUnavailable(e.to_string())),
+    }
+}
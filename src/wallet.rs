@@ -1,87 +1,377 @@
-use base64::{engine::general_purpose, Engine as _};
-use chacha20poly1305::aead::generic_array::GenericArray;
-use chacha20poly1305::aead::Aead;
-use chacha20poly1305::{ChaCha20Poly1305, KeyInit};
+use crate::address;
+use crate::hd_wallet::{HdWallet, SEED_LENGTH};
+use crate::keystore::{load_secret_encrypted, save_secret_encrypted, Kdf, SecretKind};
+use crate::wallet_manager::WalletError;
 use k256::ecdsa::signature::{Signer, Verifier};
 use k256::ecdsa::{Signature, SigningKey, VerifyingKey};
-use k256::elliptic_curve::rand_core::{OsRng, RngCore};
-use pbkdf2::pbkdf2_hmac;
-use sha2::Sha256;
-use std::fs::{read_to_string, File};
-use std::io::{Read, Write};
+use k256::elliptic_curve::bigint::U256;
+use k256::elliptic_curve::group::Group;
+use k256::elliptic_curve::ops::{LinearCombinationExt, Reduce};
+use k256::elliptic_curve::point::DecompactPoint;
+use k256::elliptic_curve::rand_core::OsRng;
+use k256::elliptic_curve::PrimeField;
+use k256::{schnorr, AffinePoint, FieldBytes, NonZeroScalar, ProjectivePoint, Scalar};
+use serde::{Deserialize, Serialize};
+use sha2::{Digest, Sha256};
+use tracing::info;
+
+// WIF-подобный формат: Base58Check(0x80 || ключ || 0x01), как у сжатых ключей Bitcoin
+const WIF_VERSION: u8 = 0x80;
+const WIF_COMPRESSED_FLAG: u8 = 0x01;
+
+const SCHNORR_CHALLENGE_TAG: &[u8] = b"BIP0340/challenge";
+
+// подписываемые данные транзакции начинаются с адреса отправителя, а сообщения - с непечатного
+// байта, поэтому подпись сообщения нельзя выдать за подпись транзакции
+const MESSAGE_PREFIX: &str = "\x19Signed Message:\n";
+
+// схема подписи транзакции. ключ и адрес у обеих схем общие: для Шнорра (BIP340)
+// из сжатого открытого ключа берется только координата x
+#[derive(Clone, Copy, Debug, Default, PartialEq, Serialize, Deserialize, clap::ValueEnum)]
+#[serde(rename_all = "lowercase")]
+pub enum SignatureScheme {
+    #[default]
+    Ecdsa,
+    Schnorr,
+}
+
+#[derive(Clone, Copy, Debug, Default, Deserialize, clap::ValueEnum)]
+#[serde(rename_all = "lowercase")]
+pub enum KeyFormat {
+    Hex,
+    #[default]
+    Wif,
+}
 
 pub struct Wallet {
     pub public_key: VerifyingKey,
     pub private_key: SigningKey,
 }
 
+// подписанное сообщение вместе со всем, что нужно для проверки владения адресом
+#[derive(Serialize, Deserialize)]
+pub struct SignedMessage {
+    pub address: String,
+    pub message: String,
+    // сжатый открытый ключ и подпись в hex
+    pub public_key: String,
+    pub signature: String,
+    #[serde(default)]
+    pub signature_type: SignatureScheme,
+}
+
 impl Wallet {
-    pub fn new() -> Self {
-        let private_key = SigningKey::random(&mut OsRng);
-        let public_key = private_key.verifying_key().clone();
-        Wallet {private_key, public_key}
+    pub fn address(&self, prefix: &str) -> String {
+        address::from_public_key(&self.public_key.to_sec1_bytes(), prefix)
     }
 
-    pub fn address(&self) -> String {
-        hex::encode(self.public_key.to_sec1_bytes())
+    pub fn sign(&self, transaction_data: &[u8], scheme: SignatureScheme) -> Vec<u8> {
+        match scheme {
+            SignatureScheme::Ecdsa => {
+                let signature: Signature = self.private_key.sign(transaction_data);
+                signature.to_vec()
+            }
+            SignatureScheme::Schnorr => {
+                let signing_key = schnorr::SigningKey::from_bytes(&self.private_key.to_bytes()).unwrap();
+                let signature: schnorr::Signature = signing_key.sign(transaction_data);
+                signature.to_bytes().to_vec()
+            }
+        }
     }
 
-    pub fn sign(&self, transaction_data: &[u8]) -> Vec<u8> {
-        let signature: Signature = self.private_key.sign(transaction_data);
-        signature.to_vec()
+    pub fn verify(public_key: &[u8], transaction_data: &[u8], signature: &[u8], scheme: SignatureScheme) -> bool {
+        // ключ и подпись приходят в том числе от чужих узлов, поэтому не паникуем на мусоре
+        match scheme {
+            SignatureScheme::Ecdsa => {
+                let Ok(verifying_key) = VerifyingKey::from_sec1_bytes(public_key) else {
+                    return false;
+                };
+                let Ok(signature) = Signature::try_from(signature) else {
+                    return false;
+                };
+                verifying_key.verify(transaction_data, &signature).is_ok()
+            }
+            SignatureScheme::Schnorr => {
+                let Some(verifying_key) = schnorr_key(public_key) else {
+                    return false;
+                };
+                let Ok(signature) = schnorr::Signature::try_from(signature) else {
+                    return false;
+                };
+                verifying_key.verify(transaction_data, &signature).is_ok()
+            }
+        }
     }
 
-    pub fn verify(public_key: &[u8], transaction_data: &[u8], signature: &[u8]) -> bool {
-        let verifying_key = VerifyingKey::from_sec1_bytes(public_key).unwrap();
-        let signature = Signature::try_from(signature).unwrap();
-        verifying_key.verify(transaction_data, &signature).is_ok()
+    // пакетная проверка подписей Шнорра (ключ, данные, подпись): со случайными коэффициентами a_i
+    // проверяется одно равенство (сумма a_i * s_i) * G = сумма a_i * R_i + сумма a_i * e_i * P_i
+    // вместо отдельной проверки каждой подписи. при неудаче неизвестно, какая подпись неверна
+    pub fn verify_schnorr_batch(batch: &[(&[u8], &[u8], &[u8])]) -> bool {
+        let mut terms = Vec::with_capacity(batch.len() * 2 + 1);
+        let mut s_sum = Scalar::ZERO;
+
+        for (i, (public_key, transaction_data, signature)) in batch.iter().enumerate() {
+            let Some(verifying_key) = schnorr_key(public_key) else {
+                return false;
+            };
+            // разбор подписи проверяет, что r - координата точки кривой, а s меньше порядка группы
+            let Ok(signature) = schnorr::Signature::try_from(*signature) else {
+                return false;
+            };
+            let signature = signature.to_bytes();
+            let (r_bytes, s_bytes) = signature.split_at(32);
+            let Some(r_point) = Option::<AffinePoint>::from(AffinePoint::decompact(FieldBytes::from_slice(r_bytes))) else {
+                return false;
+            };
+            let s = Option::<Scalar>::from(Scalar::from_repr(*FieldBytes::from_slice(s_bytes))).unwrap();
+
+            // подпись ставится на SHA256 от данных, как в schnorr::SigningKey::sign
+            let tag = Sha256::digest(SCHNORR_CHALLENGE_TAG);
+            let challenge = Sha256::new()
+                .chain_update(tag)
+                .chain_update(tag)
+                .chain_update(r_bytes)
+                .chain_update(verifying_key.to_bytes())
+                .chain_update(Sha256::digest(transaction_data))
+                .finalize();
+            let e = <Scalar as Reduce<U256>>::reduce_bytes(&challenge);
+
+            let a = if i == 0 { Scalar::ONE } else { *NonZeroScalar::random(&mut OsRng) };
+            s_sum += a * s;
+            terms.push((ProjectivePoint::from(r_point), a));
+            terms.push((ProjectivePoint::from(*verifying_key.as_affine()), a * e));
+        }
+
+        terms.push((ProjectivePoint::GENERATOR, -s_sum));
+        ProjectivePoint::lincomb_ext(terms.as_slice()).is_identity().into()
     }
 
-    pub fn save_to_file_encrypted(&self, filename: &str, password: &str) {
-        let private_key_bytes = self.private_key.to_bytes();
+    pub fn sign_message(&self, message: &str, network_id: &str, prefix: &str, scheme: SignatureScheme) -> SignedMessage {
+        let public_key = self.public_key.to_sec1_bytes();
+        SignedMessage {
+            address: self.address(prefix),
+            message: message.to_string(),
+            public_key: hex::encode(&public_key),
+            signature: hex::encode(self.sign(&message_data(network_id, message), scheme)),
+            signature_type: scheme,
+        }
+    }
 
-        let mut salt = [0u8; 16];
-        let mut nonce = [0u8; 12];
-        OsRng.fill_bytes(&mut salt);
-        OsRng.fill_bytes(&mut nonce);
+    pub fn save_to_file_encrypted(&self, filename: &str, password: &str, prefix: &str, kdf: Kdf) -> Result<(), WalletError> {
+        save_secret_encrypted(
+            filename,
+            password,
+            self.private_key.to_bytes().as_slice(),
+            SecretKind::Key,
+            &self.address(prefix),
+            kdf,
+        )?;
+        info!(file = filename, "кошелек зашифрован и записан");
+        Ok(())
+    }
 
-        let mut key = [0u8; 32];
-        pbkdf2_hmac::<Sha256>(password.as_bytes(), &salt, 100_000, &mut key);
+    pub fn from_signing_key(private_key: SigningKey) -> Self {
+        let public_key = *private_key.verifying_key();
+        Wallet { private_key, public_key }
+    }
 
-        let cipher = ChaCha20Poly1305::new(GenericArray::from_slice(&key));
-        let cipher_text = cipher.encrypt(GenericArray::from_slice(&nonce), private_key_bytes.as_slice())
-            .expect("Ошибка шифрования");
+    pub fn export_private_key(&self, format: KeyFormat) -> String {
+        let key = self.private_key.to_bytes();
+        match format {
+            KeyFormat::Hex => hex::encode(key),
+            KeyFormat::Wif => {
+                let mut data = key.to_vec();
+                data.push(WIF_COMPRESSED_FLAG);
+                bs58::encode(data).with_check_version(WIF_VERSION).into_string()
+            }
+        }
+    }
 
-        let mut file = File::create(filename).expect("Не удалось создать файл");
-        writeln!(file, "{}", general_purpose::STANDARD.encode(&salt)).unwrap();
-        writeln!(file, "{}", general_purpose::STANDARD.encode(&nonce)).unwrap();
-        writeln!(file, "{}", general_purpose::STANDARD.encode(&cipher_text)).unwrap();
+    // принимает ключ в hex или в WIF-подобном формате с контрольной суммой
+    pub fn import_private_key(private_key: &str) -> Option<Self> {
+        let private_key = private_key.trim();
+        let key = match hex::decode(private_key) {
+            Ok(key) => key,
+            Err(_) => {
+                let data = bs58::decode(private_key).with_check(Some(WIF_VERSION)).into_vec().ok()?;
+                match data.as_slice() {
+                    [WIF_VERSION, key @ .., WIF_COMPRESSED_FLAG] => key.to_vec(),
+                    _ => return None,
+                }
+            }
+        };
+        let signing_key = SigningKey::try_from(key.as_slice()).ok()?;
+        Some(Wallet::from_signing_key(signing_key))
+    }
+}
 
-        println!("Кошелек зашифрован и записан в файл {}", filename);
+impl SignedMessage {
+    pub fn verify(&self, network_id: &str, prefix: &str) -> bool {
+        let (Ok(public_key), Ok(signature)) = (hex::decode(&self.public_key), hex::decode(&self.signature)) else {
+            return false;
+        };
+        address::from_public_key(&public_key, prefix) == self.address
+            && Wallet::verify(&public_key, &message_data(network_id, &self.message), &signature, self.signature_type)
     }
+}
 
-    pub fn load_from_file_encrypted(filename: &str, password: &str) -> Self {
-        let file_content = read_to_string(filename).expect("Не удалось открыть файл");
-        let mut lines = file_content.lines();
+// длина перед текстом не дает перенести подпись на сообщение с другой границей префикса,
+// а идентификатор сети - на другую цепочку
+fn message_data(network_id: &str, message: &str) -> Vec<u8> {
+    format!("{}{}\n{}:{}", MESSAGE_PREFIX, network_id, message.len(), message).into_bytes()
+}
 
-        let salt = general_purpose::STANDARD.decode(lines.next().unwrap()).unwrap();
-        let nonce = general_purpose::STANDARD.decode(lines.next().unwrap()).unwrap();
-        let cipher_text = general_purpose::STANDARD.decode(lines.next().unwrap()).unwrap();
+// ключ Шнорра из сжатого ключа SEC1: координата x точки с четной y
+fn schnorr_key(public_key: &[u8]) -> Option<schnorr::VerifyingKey> {
+    VerifyingKey::from_sec1_bytes(public_key).ok()?;
+    if public_key.len() != 33 {
+        return None;
+    }
+    schnorr::VerifyingKey::from_bytes(&public_key[1..]).ok()
+}
+
+// содержимое файла кошелька: один ключ или seed HD-кошелька
+pub enum StoredWallet {
+    Key(Box<Wallet>),
+    Seed(HdWallet),
+}
 
-        let mut key = [0u8; 32];
-        pbkdf2_hmac::<Sha256>(password.as_bytes(), &salt, 100_000, &mut key);
+impl StoredWallet {
+    pub fn load(filename: &str, password: &str) -> Result<Self, WalletError> {
+        let secret = load_secret_encrypted(filename, password)?;
+        if secret.len() == SEED_LENGTH {
+            return Ok(StoredWallet::Seed(HdWallet::from_seed(&secret)));
+        }
 
-        let cipher = ChaCha20Poly1305::new(GenericArray::from_slice(&key));
-        let decoded_key = cipher.decrypt(GenericArray::from_slice(&nonce), &cipher_text[..])
-            .expect("Ошибка шифрования");
+        let signing_key = SigningKey::try_from(secret.as_slice()).map_err(|_| WalletError::InvalidKey)?;
+        Ok(StoredWallet::Key(Box::new(Wallet::from_signing_key(signing_key))))
+    }
 
-        let signing_key = SigningKey::try_from(decoded_key.as_slice()).expect("Некорректный ключ");
-        let verifying_key = signing_key.verifying_key().clone();
+    pub fn save_to_file_encrypted(&self, filename: &str, password: &str, prefix: &str, kdf: Kdf) -> Result<(), WalletError> {
+        match self {
+            StoredWallet::Key(wallet) => wallet.save_to_file_encrypted(filename, password, prefix, kdf),
+            StoredWallet::Seed(wallet) => wallet.save_to_file_encrypted(filename, password, prefix, kdf),
+        }
+    }
 
-        Wallet {
-            private_key: signing_key,
-            public_key: verifying_key,
+    pub fn kind(&self) -> SecretKind {
+        match self {
+            StoredWallet::Key(_) => SecretKind::Key,
+            StoredWallet::Seed(_) => SecretKind::Seed,
         }
     }
 
-}
\ No newline at end of file
+    // у кошелька из одного ключа есть только нулевой адрес
+    pub fn account(&self, index: u32) -> Option<Wallet> {
+        match self {
+            StoredWallet::Key(wallet) if index == 0 => Some(Wallet::from_signing_key(wallet.private_key.clone())),
+            StoredWallet::Key(_) => None,
+            StoredWallet::Seed(wallet) => wallet.derive(index),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    struct Signed {
+        public_key: Vec<u8>,
+        data: Vec<u8>,
+        signature: Vec<u8>,
+    }
+
+    fn signed(count: usize) -> Vec<Signed> {
+        (0..count)
+            .map(|i| {
+                let wallet = Wallet::from_signing_key(SigningKey::random(&mut OsRng));
+                let data = format!("транзакция {}", i).into_bytes();
+                Signed {
+                    public_key: wallet.public_key.to_sec1_bytes().to_vec(),
+                    signature: wallet.sign(&data, SignatureScheme::Schnorr),
+                    data,
+                }
+            })
+            .collect()
+    }
+
+    fn batch(signed: &[Signed]) -> Vec<(&[u8], &[u8], &[u8])> {
+        signed
+            .iter()
+            .map(|signed| (signed.public_key.as_slice(), signed.data.as_slice(), signed.signature.as_slice()))
+            .collect()
+    }
+
+    fn single(signed: &Signed) -> bool {
+        Wallet::verify(&signed.public_key, &signed.data, &signed.signature, SignatureScheme::Schnorr)
+    }
+
+    // s + 1 остается корректным скаляром, поэтому подпись проходит разбор и отклоняется уравнением
+    fn tamper_s(signature: &mut [u8]) {
+        let s = Option::<Scalar>::from(Scalar::from_repr(*FieldBytes::from_slice(&signature[32..]))).unwrap();
+        signature[32..].copy_from_slice(&(s + Scalar::ONE).to_repr());
+    }
+
+    #[test]
+    fn accepts_valid_batch() {
+        let signed = signed(8);
+        assert!(Wallet::verify_schnorr_batch(&batch(&signed)));
+    }
+
+    #[test]
+    fn accepts_empty_batch() {
+        assert!(Wallet::verify_schnorr_batch(&[]));
+    }
+
+    #[test]
+    fn rejects_tampered_s() {
+        for position in [0, 5] {
+            let mut signed = signed(6);
+            tamper_s(&mut signed[position].signature);
+            assert!(!Wallet::verify_schnorr_batch(&batch(&signed)));
+        }
+    }
+
+    #[test]
+    fn rejects_tampered_r() {
+        let mut swapped = signed(6);
+        // r другой подписи - координата существующей точки, так что подпись проходит разбор
+        let other_r = swapped[1].signature[..32].to_vec();
+        swapped[3].signature[..32].copy_from_slice(&other_r);
+        assert!(!Wallet::verify_schnorr_batch(&batch(&swapped)));
+
+        let mut flipped = signed(6);
+        flipped[2].signature[0] ^= 1;
+        assert!(!Wallet::verify_schnorr_batch(&batch(&flipped)));
+    }
+
+    #[test]
+    fn rejects_wrong_message() {
+        let mut signed = signed(6);
+        signed[4].data.push(b'!');
+        assert!(!Wallet::verify_schnorr_batch(&batch(&signed)));
+    }
+
+    #[test]
+    fn rejects_wrong_key() {
+        let mut signed = signed(6);
+        signed[0].public_key = signed[1].public_key.clone();
+        assert!(!Wallet::verify_schnorr_batch(&batch(&signed)));
+    }
+
+    #[test]
+    fn single_signature_agrees_with_verify() {
+        let mut cases = signed(5);
+        tamper_s(&mut cases[1].signature);
+        let other_r = cases[0].signature[..32].to_vec();
+        cases[2].signature[..32].copy_from_slice(&other_r);
+        cases[3].data.push(b'!');
+        cases[4].signature.truncate(63);
+        cases.extend(signed(3));
+
+        for signed in &cases {
+            assert_eq!(Wallet::verify_schnorr_batch(&batch(std::slice::from_ref(signed))), single(signed));
+        }
+        assert_eq!(cases.iter().filter(|signed| single(signed)).count(), 4);
+    }
+}
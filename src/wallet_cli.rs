@@ -0,0 +1,148 @@
+// команды для работы с файлами кошельков без запуска узла.
+// пароли и ключи читаются из stdin, чтобы не оставались в истории команд
+
+use crate::chain_spec::ChainSpec;
+use crate::i18n::{fail, Message};
+use crate::keystore::{Kdf, KdfKind};
+use crate::wallet::{KeyFormat, SignatureScheme, SignedMessage, StoredWallet, Wallet};
+use crate::wallet_manager::WalletError;
+use clap::Subcommand;
+use std::fs::{read_to_string, write};
+use std::io::{stdin, stdout, Write};
+use std::path::Path;
+
+#[derive(Subcommand)]
+pub enum WalletCommand {
+    /// Перешифровать кошелек новым паролем
+    ChangePassword {
+        #[arg(long)]
+        file: String,
+        #[arg(long, value_enum, default_value_t)]
+        kdf: KdfKind,
+        #[arg(long)]
+        spec: Option<String>,
+    },
+    /// Показать закрытый ключ кошелька
+    Export {
+        #[arg(long)]
+        file: String,
+        /// Номер адреса в HD-кошельке
+        #[arg(long, default_value_t = 0)]
+        account: u32,
+        #[arg(long, value_enum, default_value_t)]
+        format: KeyFormat,
+    },
+    /// Создать зашифрованный кошелек из закрытого ключа
+    Import {
+        #[arg(long)]
+        file: String,
+        #[arg(long, value_enum, default_value_t)]
+        kdf: KdfKind,
+        #[arg(long)]
+        spec: Option<String>,
+    },
+    /// Подписать сообщение, чтобы доказать владение адресом
+    SignMessage {
+        #[arg(long)]
+        file: String,
+        /// Номер адреса в HD-кошельке
+        #[arg(long, default_value_t = 0)]
+        account: u32,
+        #[arg(long)]
+        message: String,
+        #[arg(long, value_enum, default_value_t)]
+        signature_type: SignatureScheme,
+        /// Куда записать подписанное сообщение, по умолчанию оно выводится на экран
+        #[arg(long)]
+        out: Option<String>,
+        #[arg(long)]
+        spec: Option<String>,
+    },
+    /// Проверить подписанное сообщение
+    VerifyMessage {
+        #[arg(long)]
+        signed: String,
+        #[arg(long)]
+        spec: Option<String>,
+    },
+}
+
+pub fn run(command: WalletCommand) {
+    match command {
+        WalletCommand::ChangePassword { file, kdf, spec } => {
+            let stored = load_wallet(&file, Message::PromptCurrentPassword);
+            let new_password = read_new_password();
+            let prefix = ChainSpec::load_or_default(spec).address_prefix;
+            // файл обычного или HD-кошелька перешифровывается новым паролем
+            stored
+                .save_to_file_encrypted(&file, &new_password, &prefix, Kdf::recommended(kdf))
+                .unwrap_or_else(|e| fail(e));
+        }
+        WalletCommand::Export { file, account, format } => {
+            let wallet = account_wallet(&load_wallet(&file, Message::PromptPassword), account);
+            println!("{}", wallet.export_private_key(format));
+        }
+        WalletCommand::Import { file, kdf, spec } => {
+            if Path::new(&file).exists() {
+                fail(Message::FileExists(file));
+            }
+            let private_key = read_line(Message::PromptPrivateKey);
+            let Some(wallet) = Wallet::import_private_key(&private_key) else {
+                fail(WalletError::InvalidKey);
+            };
+            let password = read_new_password();
+            let prefix = ChainSpec::load_or_default(spec).address_prefix;
+            wallet
+                .save_to_file_encrypted(&file, &password, &prefix, Kdf::recommended(kdf))
+                .unwrap_or_else(|e| fail(e));
+            println!("{}", Message::WalletAddress(wallet.address(&prefix)));
+        }
+        WalletCommand::SignMessage { file, account, message, signature_type, out, spec } => {
+            let wallet = account_wallet(&load_wallet(&file, Message::PromptPassword), account);
+            let spec = ChainSpec::load_or_default(spec);
+            let signed = wallet.sign_message(&message, &spec.network_id, &spec.address_prefix, signature_type);
+            let json = serde_json::to_string_pretty(&signed).unwrap();
+            match out {
+                Some(out) => write(&out, json).unwrap_or_else(|e| fail(Message::WriteFailed(out.clone(), e.to_string()))),
+                None => println!("{}", json),
+            }
+        }
+        WalletCommand::VerifyMessage { signed, spec } => {
+            let json = read_to_string(&signed).unwrap_or_else(|e| fail(Message::OpenFailed(signed.clone(), e.to_string())));
+            let signed: SignedMessage =
+                serde_json::from_str(&json).unwrap_or_else(|e| fail(Message::InvalidSignatureFile(e.to_string())));
+            let spec = ChainSpec::load_or_default(spec);
+            if !signed.verify(&spec.network_id, &spec.address_prefix) {
+                fail(Message::SignatureInvalid);
+            }
+            println!("{}", Message::SignatureValid(signed.address));
+        }
+    }
+}
+
+fn load_wallet(file: &str, prompt: Message) -> StoredWallet {
+    let password = read_line(prompt);
+    StoredWallet::load(file, &password).unwrap_or_else(|e| fail(e))
+}
+
+// у кошелька из одного ключа есть только адрес 0
+fn account_wallet(stored: &StoredWallet, account: u32) -> Wallet {
+    stored.account(account).unwrap_or_else(|| fail(WalletError::UnknownAccount(account)))
+}
+
+fn read_new_password() -> String {
+    let password = read_line(Message::PromptNewPassword);
+    if password != read_line(Message::PromptRepeatPassword) {
+        fail(Message::PasswordsMismatch);
+    }
+    password
+}
+
+pub fn read_line(prompt: Message) -> String {
+    print!("{}", prompt);
+    stdout().flush().unwrap();
+    let mut line = String::new();
+    stdin().read_line(&mut line).unwrap();
+    line.trim_end_matches(['\r', '\n']).to_string()
+}
+
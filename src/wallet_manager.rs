@@ -0,0 +1,482 @@
+// каталог кошельков узла. каждый кошелек - подкаталог с файлом ключа и списком адресов:
+//   <каталог>/<имя>/keystore.json - зашифрованный ключ или seed
+//   <каталог>/<имя>/accounts.json - номера, адреса и метки, читаются без пароля
+// ключи расшифровываются только при разблокировке и хранятся в памяти до истечения сессии.
+// у кошелька наблюдения и мультиподписного кошелька нет файла ключа, только список адресов
+
+use crate::address;
+use crate::hd_wallet::HdWallet;
+use crate::i18n::{self, Localize};
+use crate::keystore::{Kdf, SecretKind};
+use crate::transaction::{Multisig, Transaction};
+use crate::wallet::{KeyFormat, StoredWallet, Wallet};
+use k256::ecdsa::VerifyingKey;
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+use std::fmt::{Display, Formatter};
+use std::fs::{create_dir, create_dir_all, read_dir, read_to_string, remove_dir_all, rename, write};
+use std::io::ErrorKind;
+use std::path::{Path, PathBuf};
+use std::sync::{Mutex, MutexGuard, PoisonError};
+use std::time::{Duration, Instant};
+
+const KEYSTORE_FILE: &str = "keystore.json";
+const ACCOUNTS_FILE: &str = "accounts.json";
+pub const MAX_NAME_LENGTH: usize = 64;
+pub const DEFAULT_UNLOCK_TIMEOUT: Duration = Duration::from_secs(300);
+pub const MAX_UNLOCK_TIMEOUT: Duration = Duration::from_secs(3600);
+
+#[derive(Debug)]
+pub enum WalletError {
+    InvalidName,
+    NotFound,
+    AlreadyExists,
+    WrongPassword,
+    UnreadableFile(String),
+    InvalidFile(String),
+    UnsupportedVersion(u32),
+    WriteFailed(String),
+    Locked,
+    UnknownAccount(u32),
+    SingleAccount,
+    InvalidMnemonic(bip39::Error),
+    InvalidKey,
+    NoPrivateKeys,
+    NotWatchOnly,
+    InvalidWatchTarget,
+    DuplicateAddress,
+    InvalidPublicKey,
+    InvalidMultisig,
+    NotMultisig,
+    NotMultisigTransaction,
+    KeyNotInMultisig,
+}
+
+impl Display for WalletError {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{}", self.text(i18n::lang()))
+    }
+}
+
+#[derive(Clone, Copy, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum WalletKind {
+    Key,
+    Seed,
+    // кошелек наблюдения: адреса и открытые ключи без закрытых
+    Watch,
+    // мультиподписной адрес; подписывают владельцы ключей из своих кошельков
+    Multisig,
+}
+
+#[derive(Clone, Serialize, Deserialize)]
+pub struct MultisigConfig {
+    pub threshold: u8,
+    // сжатые открытые ключи в hex в порядке, из которого получен адрес
+    pub public_keys: Vec<String>,
+}
+
+#[derive(Clone, Serialize, Deserialize)]
+pub struct Account {
+    pub index: u32,
+    pub address: String,
+    pub label: Option<String>,
+    // открытый ключ в hex; его передают участникам при создании мультиподписи.
+    // нет у мультиподписного адреса и у адреса наблюдения, добавленного без ключа
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub public_key: Option<String>,
+}
+
+#[derive(Serialize)]
+pub struct WalletInfo {
+    pub name: String,
+    pub kind: WalletKind,
+    pub accounts: Vec<Account>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub multisig: Option<MultisigConfig>,
+    // сколько секунд осталось до блокировки, если кошелек разблокирован
+    pub unlocked_for: Option<u64>,
+}
+
+#[derive(Serialize, Deserialize)]
+struct WalletMetadata {
+    kind: WalletKind,
+    accounts: Vec<Account>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    multisig: Option<MultisigConfig>,
+}
+
+struct Session {
+    wallet: StoredWallet,
+    expires_at: Instant,
+}
+
+pub struct WalletManager {
+    dir: PathBuf,
+    prefix: String,
+    sessions: Mutex<HashMap<String, Session>>,
+    // упорядочивает изменения списков адресов; файлы читаются и пишутся без блокировки сессий
+    metadata_updates: Mutex<()>,
+}
+
+impl WalletManager {
+    pub fn new(dir: &str, prefix: &str) -> Result<Self, WalletError> {
+        create_dir_all(dir).map_err(|e| WalletError::WriteFailed(format!("{}: {}", dir, e)))?;
+        Ok(WalletManager {
+            dir: PathBuf::from(dir),
+            prefix: prefix.to_string(),
+            sessions: Mutex::new(HashMap::new()),
+            metadata_updates: Mutex::new(()),
+        })
+    }
+
+    pub fn list(&self) -> Result<Vec<WalletInfo>, WalletError> {
+        let sessions = self.sessions();
+        let now = Instant::now();
+
+        let mut names = read_dir(&self.dir)
+            .map_err(|e| WalletError::UnreadableFile(format!("{}: {}", self.dir.display(), e)))?
+            .filter_map(|entry| entry.ok())
+            .filter(|entry| entry.path().join(ACCOUNTS_FILE).exists())
+            .filter_map(|entry| entry.file_name().into_string().ok())
+            .collect::<Vec<String>>();
+        names.sort();
+
+        names
+            .into_iter()
+            .map(|name| {
+                let metadata = self.load_metadata(&name)?;
+                let unlocked_for = sessions.get(&name).map(|session| (session.expires_at - now).as_secs());
+                Ok(WalletInfo {
+                    name,
+                    kind: metadata.kind,
+                    accounts: metadata.accounts,
+                    multisig: metadata.multisig,
+                    unlocked_for,
+                })
+            })
+            .collect()
+    }
+
+    // новый HD-кошелек; без фразы восстановления генерируется новая и возвращается вызывающему
+    pub fn create(
+        &self,
+        name: &str,
+        password: &str,
+        mnemonic: Option<&str>,
+        kdf: Kdf,
+    ) -> Result<(Option<String>, Account), WalletError> {
+        let (wallet, mnemonic) = match mnemonic {
+            Some(phrase) => (HdWallet::from_mnemonic(phrase).map_err(WalletError::InvalidMnemonic)?, None),
+            None => {
+                let (wallet, phrase) = HdWallet::new();
+                (wallet, Some(phrase))
+            }
+        };
+        let account = self.store(name, password, StoredWallet::Seed(wallet), kdf)?;
+        Ok((mnemonic, account))
+    }
+
+    pub fn import_key(&self, name: &str, password: &str, private_key: &str, kdf: Kdf) -> Result<Account, WalletError> {
+        let wallet = Wallet::import_private_key(private_key).ok_or(WalletError::InvalidKey)?;
+        self.store(name, password, StoredWallet::Key(Box::new(wallet)), kdf)
+    }
+
+    pub fn create_watch_only(&self, name: &str) -> Result<(), WalletError> {
+        let dir = self.reserve_dir(name)?;
+        let metadata = WalletMetadata { kind: WalletKind::Watch, accounts: vec![], multisig: None };
+        self.save_metadata(name, &metadata).inspect_err(|_| release_dir(&dir))
+    }
+
+    // ключи сортируются, чтобы адрес не зависел от порядка, в котором их передали
+    pub fn create_multisig(&self, name: &str, threshold: u8, public_keys: &[String]) -> Result<Account, WalletError> {
+        let mut keys = public_keys
+            .iter()
+            .map(|key| {
+                hex::decode(key.trim())
+                    .ok()
+                    .and_then(|bytes| VerifyingKey::from_sec1_bytes(&bytes).ok())
+                    .map(|key| key.to_sec1_bytes().to_vec())
+                    .ok_or(WalletError::InvalidPublicKey)
+            })
+            .collect::<Result<Vec<Vec<u8>>, WalletError>>()?;
+        keys.sort();
+        let multisig = Multisig::new(threshold, keys);
+        if !multisig.is_well_formed() {
+            return Err(WalletError::InvalidMultisig);
+        }
+
+        let dir = self.reserve_dir(name)?;
+        let account = Account {
+            index: 0,
+            address: multisig.address(&self.prefix),
+            label: None,
+            public_key: None,
+        };
+        let config = MultisigConfig {
+            threshold,
+            public_keys: multisig.public_keys.iter().map(hex::encode).collect(),
+        };
+        let metadata = WalletMetadata {
+            kind: WalletKind::Multisig,
+            accounts: vec![account.clone()],
+            multisig: Some(config),
+        };
+        self.save_metadata(name, &metadata).inspect_err(|_| release_dir(&dir))?;
+        Ok(account)
+    }
+
+    // пустой набор подписей для новой транзакции с мультиподписного адреса
+    pub fn multisig(&self, name: &str) -> Result<Multisig, WalletError> {
+        self.wallet_dir(name)?;
+        let config = self.load_metadata(name)?.multisig.ok_or(WalletError::NotMultisig)?;
+        let public_keys = config
+            .public_keys
+            .iter()
+            .map(|key| hex::decode(key).map_err(|e| WalletError::InvalidFile(e.to_string())))
+            .collect::<Result<Vec<Vec<u8>>, WalletError>>()?;
+        Ok(Multisig::new(config.threshold, public_keys))
+    }
+
+    // параметры мультиподписного адреса из каталога, если такой кошелек есть
+    pub fn find_multisig(&self, address: &str) -> Result<Option<Multisig>, WalletError> {
+        self.list()?
+            .into_iter()
+            .find(|wallet| wallet.kind == WalletKind::Multisig && wallet.accounts[0].address == address)
+            .map(|wallet| self.multisig(&wallet.name))
+            .transpose()
+    }
+
+    // добавляет в мультиподписную транзакцию подпись ключа разблокированного кошелька
+    pub fn sign_multisig(&self, name: &str, index: u32, tx: &mut Transaction) -> Result<(), WalletError> {
+        let wallet = self.signer(name, index)?;
+        let data = tx.signing_data();
+        let multisig = tx.multisig.as_mut().ok_or(WalletError::NotMultisigTransaction)?;
+        if !multisig.sign(&wallet, data.as_bytes(), tx.signature_type) {
+            return Err(WalletError::KeyNotInMultisig);
+        }
+        Ok(())
+    }
+
+    // адрес для наблюдения задается самим адресом или открытым ключом, из которого он получается
+    pub fn watch(&self, name: &str, target: &str, label: Option<String>) -> Result<Account, WalletError> {
+        self.wallet_dir(name)?;
+        let target = target.trim();
+        let (address, public_key) = if address::is_valid(target, &self.prefix) {
+            (target.to_string(), None)
+        } else {
+            let public_key = hex::decode(target)
+                .ok()
+                .and_then(|bytes| VerifyingKey::from_sec1_bytes(&bytes).ok())
+                .ok_or(WalletError::InvalidWatchTarget)?
+                .to_sec1_bytes();
+            (address::from_public_key(&public_key, &self.prefix), Some(hex::encode(public_key)))
+        };
+
+        let _updates = self.metadata_updates();
+        let mut metadata = self.load_metadata(name)?;
+        if metadata.kind != WalletKind::Watch {
+            return Err(WalletError::NotWatchOnly);
+        }
+        if metadata.accounts.iter().any(|account| account.address == address) {
+            return Err(WalletError::DuplicateAddress);
+        }
+        let index = metadata.accounts.iter().map(|account| account.index + 1).max().unwrap_or(0);
+        let account = Account { index, address, label, public_key };
+        metadata.accounts.push(account.clone());
+        self.save_metadata(name, &metadata)?;
+        Ok(account)
+    }
+
+    // возвращает фактическое время разблокировки
+    pub fn unlock(&self, name: &str, password: &str, timeout: Duration) -> Result<Duration, WalletError> {
+        let wallet = self.decrypt(name, password)?;
+        let timeout = timeout.min(MAX_UNLOCK_TIMEOUT);
+        self.sessions().insert(
+            name.to_string(),
+            Session { wallet, expires_at: Instant::now() + timeout },
+        );
+        Ok(timeout)
+    }
+
+    pub fn lock(&self, name: &str) -> Result<(), WalletError> {
+        self.wallet_dir(name)?;
+        self.sessions().remove(name);
+        Ok(())
+    }
+
+    // вызывается периодически, чтобы ключи не оставались в памяти после истечения сессии
+    pub fn lock_expired(&self) {
+        drop(self.sessions());
+    }
+
+    // следующий адрес HD-кошелька; для вычисления нужен seed, поэтому кошелек должен быть разблокирован
+    pub fn add_account(&self, name: &str, label: Option<String>) -> Result<Account, WalletError> {
+        self.wallet_dir(name)?;
+        let _updates = self.metadata_updates();
+        let mut metadata = self.load_metadata(name)?;
+        if matches!(metadata.kind, WalletKind::Watch | WalletKind::Multisig) {
+            return Err(WalletError::NoPrivateKeys);
+        }
+        let index = metadata.accounts.iter().map(|account| account.index + 1).max().unwrap_or(0);
+        let derived = {
+            let sessions = self.sessions();
+            let session = sessions.get(name).ok_or(WalletError::Locked)?;
+            let StoredWallet::Seed(wallet) = &session.wallet else {
+                return Err(WalletError::SingleAccount);
+            };
+            wallet.derive(index).ok_or(WalletError::InvalidKey)?
+        };
+        let account = Account {
+            index,
+            address: derived.address(&self.prefix),
+            label,
+            public_key: Some(hex::encode(derived.public_key.to_sec1_bytes())),
+        };
+        metadata.accounts.push(account.clone());
+        self.save_metadata(name, &metadata)?;
+        Ok(account)
+    }
+
+    pub fn set_label(&self, name: &str, index: u32, label: Option<String>) -> Result<Account, WalletError> {
+        self.wallet_dir(name)?;
+        let _updates = self.metadata_updates();
+        let mut metadata = self.load_metadata(name)?;
+        let account = metadata
+            .accounts
+            .iter_mut()
+            .find(|account| account.index == index)
+            .ok_or(WalletError::UnknownAccount(index))?;
+        account.label = label;
+        let account = account.clone();
+        self.save_metadata(name, &metadata)?;
+        Ok(account)
+    }
+
+    pub fn change_password(&self, name: &str, password: &str, new_password: &str, kdf: Kdf) -> Result<(), WalletError> {
+        let wallet = self.decrypt(name, password)?;
+        let keystore = self.wallet_dir(name)?.join(KEYSTORE_FILE);
+        wallet.save_to_file_encrypted(keystore.to_str().unwrap(), new_password, &self.prefix, kdf)
+    }
+
+    // выгрузка ключа всегда требует пароль, даже у разблокированного кошелька
+    pub fn export_key(&self, name: &str, password: &str, index: u32, format: KeyFormat) -> Result<String, WalletError> {
+        let wallet = self.decrypt(name, password)?;
+        self.check_account(name, index)?;
+        let account = wallet.account(index).ok_or(WalletError::UnknownAccount(index))?;
+        Ok(account.export_private_key(format))
+    }
+
+    // ключ адреса для подписи транзакции из разблокированного кошелька
+    pub fn signer(&self, name: &str, index: u32) -> Result<Wallet, WalletError> {
+        self.check_account(name, index)?;
+        let sessions = self.sessions();
+        let session = sessions.get(name).ok_or(WalletError::Locked)?;
+        session.wallet.account(index).ok_or(WalletError::UnknownAccount(index))
+    }
+
+    fn store(&self, name: &str, password: &str, wallet: StoredWallet, kdf: Kdf) -> Result<Account, WalletError> {
+        let first = wallet.account(0).ok_or(WalletError::InvalidKey)?;
+        let dir = self.reserve_dir(name)?;
+        // KDF и запись файлов идут без блокировок, чтобы создание кошелька не задерживало остальные запросы
+        let keystore = dir.join(KEYSTORE_FILE);
+        wallet
+            .save_to_file_encrypted(keystore.to_str().unwrap(), password, &self.prefix, kdf)
+            .inspect_err(|_| release_dir(&dir))?;
+        let account = Account {
+            index: 0,
+            address: first.address(&self.prefix),
+            label: None,
+            public_key: Some(hex::encode(first.public_key.to_sec1_bytes())),
+        };
+        let kind = match wallet.kind() {
+            SecretKind::Key => WalletKind::Key,
+            SecretKind::Seed => WalletKind::Seed,
+        };
+        let metadata = WalletMetadata { kind, accounts: vec![account.clone()], multisig: None };
+        self.save_metadata(name, &metadata).inspect_err(|_| release_dir(&dir))?;
+        Ok(account)
+    }
+
+    fn decrypt(&self, name: &str, password: &str) -> Result<StoredWallet, WalletError> {
+        let keystore = self.wallet_dir(name)?.join(KEYSTORE_FILE);
+        if matches!(self.load_metadata(name)?.kind, WalletKind::Watch | WalletKind::Multisig) {
+            return Err(WalletError::NoPrivateKeys);
+        }
+        StoredWallet::load(keystore.to_str().unwrap(), password)
+    }
+
+    fn check_account(&self, name: &str, index: u32) -> Result<(), WalletError> {
+        self.wallet_dir(name)?;
+        let metadata = self.load_metadata(name)?;
+        if matches!(metadata.kind, WalletKind::Watch | WalletKind::Multisig) {
+            return Err(WalletError::NoPrivateKeys);
+        }
+        if !metadata.accounts.iter().any(|account| account.index == index) {
+            return Err(WalletError::UnknownAccount(index));
+        }
+        Ok(())
+    }
+
+    // имя кошелька приходит от клиента, поэтому в путь попадают только безопасные символы
+    fn valid_dir(&self, name: &str) -> Result<PathBuf, WalletError> {
+        let valid = !name.is_empty()
+            && name.len() <= MAX_NAME_LENGTH
+            && name.chars().all(|c| c.is_ascii_alphanumeric() || c == '-' || c == '_');
+        if !valid {
+            return Err(WalletError::InvalidName);
+        }
+        Ok(self.dir.join(name))
+    }
+
+    // каталог создается атомарно, поэтому два запроса не создадут кошелек с одним именем.
+    // кошелек виден остальным методам, только когда записан список адресов
+    fn reserve_dir(&self, name: &str) -> Result<PathBuf, WalletError> {
+        let dir = self.valid_dir(name)?;
+        match create_dir(&dir) {
+            Ok(()) => Ok(dir),
+            Err(e) if e.kind() == ErrorKind::AlreadyExists => Err(WalletError::AlreadyExists),
+            Err(e) => Err(WalletError::WriteFailed(format!("{}: {}", dir.display(), e))),
+        }
+    }
+
+    fn wallet_dir(&self, name: &str) -> Result<PathBuf, WalletError> {
+        let dir = self.valid_dir(name)?;
+        if !dir.join(ACCOUNTS_FILE).exists() {
+            return Err(WalletError::NotFound);
+        }
+        Ok(dir)
+    }
+
+    fn load_metadata(&self, name: &str) -> Result<WalletMetadata, WalletError> {
+        let filename = self.dir.join(name).join(ACCOUNTS_FILE);
+        let content = read_to_string(&filename)
+            .map_err(|e| WalletError::UnreadableFile(format!("{}: {}", filename.display(), e)))?;
+        serde_json::from_str(&content).map_err(|e| WalletError::InvalidFile(format!("{}: {}", filename.display(), e)))
+    }
+
+    fn save_metadata(&self, name: &str, metadata: &WalletMetadata) -> Result<(), WalletError> {
+        let filename = self.dir.join(name).join(ACCOUNTS_FILE);
+        let tmp_filename = filename.with_extension("json.tmp");
+        let write_failed = |e: std::io::Error| WalletError::WriteFailed(format!("{}: {}", filename.display(), e));
+        write(&tmp_filename, serde_json::to_string_pretty(metadata).unwrap()).map_err(write_failed)?;
+        rename(&tmp_filename, &filename).map_err(write_failed)
+    }
+
+    fn metadata_updates(&self) -> MutexGuard<'_, ()> {
+        self.metadata_updates.lock().unwrap_or_else(PoisonError::into_inner)
+    }
+
+    // истекшие сессии удаляются при каждом обращении
+    fn sessions(&self) -> MutexGuard<'_, HashMap<String, Session>> {
+        let mut sessions = self.sessions.lock().unwrap_or_else(PoisonError::into_inner);
+        let now = Instant::now();
+        sessions.retain(|_, session| session.expires_at > now);
+        sessions
+    }
+}
+
+// освобождает имя кошелька, который не удалось записать до конца
+fn release_dir(dir: &Path) {
+    let _ = remove_dir_all(dir);
+}